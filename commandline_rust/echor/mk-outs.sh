@@ -8,3 +8,12 @@ echo    "Hello"  "there" > $OUTDIR/hello2.txt
 echo -n "Hello  there"   > $OUTDIR/hello1.n.txt
 echo -n "Hello"  "there" > $OUTDIR/hello2.n.txt
 
+echo -e 'Hello\tthere\\' 'new\nline' 'unknown\q' > $OUTDIR/escapes.e.txt
+echo -E 'Hello\tthere\\' 'new\nline' 'unknown\q' > $OUTDIR/escapes.E.txt
+echo -e 'Hello\c' 'there'                        > $OUTDIR/stop.e.txt
+echo -e '\0101\x42\x4g\0\xff\x\0777'             > $OUTDIR/bytes.e.txt
+# echo does not know \u{...}, so write the characters themselves
+printf '%s\n' 'é😀🦀\uzz\u{}'                    > $OUTDIR/unicode.e.txt
+printf '%-6s|%5d|%#x|%08.3f\n' name 42 255 -3.14159 > $OUTDIR/printf.txt
+printf '%s=%03d\n' a 1 b 2 c                        > $OUTDIR/printf.reuse.txt
+printf '\101\0101\1010\x41\x4g\"\q\\%s\n' one two          > $OUTDIR/printf.escapes.txt
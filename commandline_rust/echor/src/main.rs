@@ -1,15 +1,21 @@
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches};
+use std::error::Error;
+use std::io::{self, Write};
+
+type MyResult<T> = Result<T, Box<dyn Error>>;
 
 fn main() {
     let matches = App::new("echor")
         .version("0.1.0")
         .author("HOGE")
         .about("Rust echo")
+        // --printfの引数に負の数を渡せるようにする
+        .setting(AppSettings::AllowNegativeNumbers)
         .arg(
             Arg::with_name("text")
                 .value_name("TEXT")
                 .help("Input text")
-                .required(true)
+                .required_unless("printf")
                 .min_values(1),
         )
         .arg(
@@ -18,14 +24,636 @@ fn main() {
                 .help("Do not print newline")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("escapes")
+                .short("e")
+                .help("Enable interpretation of backslash escapes")
+                .takes_value(false)
+                .overrides_with("no_escapes"),
+        )
+        .arg(
+            Arg::with_name("no_escapes")
+                .short("E")
+                .help("Disable interpretation of backslash escapes (default)")
+                .takes_value(false)
+                .overrides_with("escapes"),
+        )
+        .arg(
+            Arg::with_name("printf")
+                .long("printf")
+                .value_name("FORMAT")
+                .help("Print the TEXT arguments according to FORMAT like printf")
+                .takes_value(true)
+                .conflicts_with_all(&["omit_newline", "escapes", "no_escapes"]),
+        )
         .get_matches();
 
+    if let Err(e) = run(&matches) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(matches: &ArgMatches) -> MyResult<()> {
     // 引数の処理
-    // "text"引数は必須であるためNoneになり得ないのでunwrap()で処理
-    let text = matches.values_of_lossy("text").unwrap();
+    // "text"引数は--printfの場合のみ省略できる
+    let text = matches.values_of_lossy("text").unwrap_or_default();
     let omit_newline = matches.is_present("omit_newline");
+    // -eと-Eは後に指定した方が優先される
+    let escapes = matches.is_present("escapes");
+
+    let output = if let Some(format) = matches.value_of("printf") {
+        printf(format, &text)?
+    } else {
+        let text = text.join(" ");
+        let (mut output, stopped) = if escapes {
+            unescape(&text)
+        } else {
+            (text.into_bytes(), false)
+        };
+        // \cで打ち切った場合は改行も出力しない
+        if !omit_newline && !stopped {
+            output.push(b'\n');
+        }
+        output
+    };
+
+    // \0NNNや\xHHは任意のバイトを出力するため文字列ではなくバイト列で書き出す
+    io::stdout().write_all(&output)?;
+    Ok(())
+}
+
+// バックスラッシュによるエスケープを解釈したバイト列を返す
+// \cが現れた場合はそこで打ち切り、2つ目の値をtrueにする
+fn unescape(text: &str) -> (Vec<u8>, bool) {
+    scan_escapes(text, Escapes::Echo).expect("echo -e escapes never fail")
+}
+
+// printf(1)のFORMATのエスケープを解釈したバイト列を返す
+// \cが現れた場合はそこで打ち切り、2つ目の値をtrueにする
+fn printf_unescape(text: &str) -> MyResult<(Vec<u8>, bool)> {
+    scan_escapes(text, Escapes::Printf)
+}
+
+// echo -eとprintfのエスケープの違い
+// echo -eの8進数は\0NNN(0〜3桁)で、\uと\Uは桁数が可変で\u{H...}も使える
+// printfの8進数は\0を付けない\NNN(1〜3桁)で、\uと\Uは4桁と8桁ちょうど。\"も使える
+// echo -eは不正なエスケープをそのまま出力し、printfはエラーにする
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escapes {
+    Echo,
+    Printf,
+}
+
+impl Escapes {
+    // モードによって異なるエスケープを解釈してoutputに書き出し、escapeより後に読み取ったバイト数を返す
+    // escapedはエスケープの文字から始まる。共通のエスケープの場合はNone
+    fn escape(self, escaped: &[u8], output: &mut Vec<u8>) -> MyResult<Option<usize>> {
+        let (escape, rest) = (escaped[0], &escaped[1..]);
+        let len = match (self, escape) {
+            // 255を超えた分は切り捨てる
+            (Escapes::Echo, b'0') => {
+                let (value, len) = parse_digits(rest, 8, 3);
+                output.push(value as u8);
+                len
+            }
+            (Escapes::Printf, b'0'..=b'7') => {
+                let (value, len) = parse_digits(escaped, 8, 3);
+                output.push(value as u8);
+                len - 1
+            }
+            (Escapes::Printf, b'"') => {
+                output.push(b'"');
+                0
+            }
+            (Escapes::Echo, b'u' | b'U') => match parse_unicode(escape, rest) {
+                Some((c, len)) => {
+                    push_char(output, c);
+                    len
+                }
+                None => {
+                    output.extend_from_slice(&[b'\\', escape]);
+                    0
+                }
+            },
+            (Escapes::Printf, b'u' | b'U') => {
+                let digits = if escape == b'u' { 4 } else { 8 };
+                let (code, len) = parse_digits(rest, 16, digits);
+                if len < digits {
+                    return Err("missing hexadecimal number in escape".into());
+                }
+                let c = char::from_u32(code).ok_or_else(|| {
+                    format!(
+                        "invalid universal character name \\{}",
+                        String::from_utf8_lossy(&escaped[..=len])
+                    )
+                })?;
+                push_char(output, c);
+                len
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(len))
+    }
+}
+
+// エスケープを解釈したバイト列を返す。モードによる違いはEscapes::escapeで解釈する
+// \cが現れた場合はそこで打ち切り、2つ目の値をtrueにする
+fn scan_escapes(text: &str, escapes: Escapes) -> MyResult<(Vec<u8>, bool)> {
+    let bytes = text.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        // 末尾のバックスラッシュはそのまま出力する
+        if bytes[i] != b'\\' || i + 1 == bytes.len() {
+            output.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let escape = bytes[i + 1];
+        i += 2;
+        if escape == b'c' {
+            return Ok((output, true));
+        }
+        if let Some(len) = escapes.escape(&bytes[i - 1..], &mut output)? {
+            i += len;
+            continue;
+        }
+        match escape {
+            b'x' => match parse_digits(&bytes[i..], 16, 2) {
+                (_, 0) if escapes == Escapes::Printf => {
+                    return Err("missing hexadecimal number in escape".into())
+                }
+                (_, 0) => output.extend_from_slice(b"\\x"),
+                (value, len) => {
+                    output.push(value as u8);
+                    i += len;
+                }
+            },
+            _ => match control_escape(escape) {
+                Some(b) => output.push(b),
+                // 未知のエスケープはバックスラッシュごと出力する
+                None => output.extend_from_slice(&[b'\\', escape]),
+            },
+        }
+    }
+    Ok((output, false))
+}
+
+fn push_char(output: &mut Vec<u8>, c: char) {
+    let mut buf = [0; 4];
+    output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+}
+
+// echo -eとprintfで共通の1文字のエスケープ
+fn control_escape(escape: u8) -> Option<u8> {
+    match escape {
+        b'\\' => Some(b'\\'),
+        b'a' => Some(0x07),
+        b'b' => Some(0x08),
+        b'e' => Some(0x1b),
+        b'f' => Some(0x0c),
+        b'n' => Some(b'\n'),
+        b'r' => Some(b'\r'),
+        b't' => Some(b'\t'),
+        b'v' => Some(0x0b),
+        _ => None,
+    }
+}
+
+// 先頭から最大max桁のradix進数を読み取り、値と読み取った桁数を返す
+fn parse_digits(bytes: &[u8], radix: u32, max: usize) -> (u32, usize) {
+    bytes
+        .iter()
+        .take(max)
+        .map_while(|&b| (b as char).to_digit(radix))
+        .fold((0, 0), |(value, len), digit| {
+            (value * radix + digit, len + 1)
+        })
+}
+
+// \uHHHH、\UHHHHHHHH、\u{H...}の\u以降を読み取り、文字と読み取ったバイト数を返す
+// 桁がない場合やサロゲートなど文字にならない場合はNone
+fn parse_unicode(escape: u8, bytes: &[u8]) -> Option<(char, usize)> {
+    let (code, len) = match (escape, bytes.first()) {
+        (b'u', Some(b'{')) => {
+            let (code, len) = parse_digits(&bytes[1..], 16, 6);
+            if len == 0 || bytes.get(len + 1) != Some(&b'}') {
+                return None;
+            }
+            (code, len + 2)
+        }
+        (b'u', _) => parse_digits(bytes, 16, 4),
+        _ => parse_digits(bytes, 16, 8),
+    };
+    if len == 0 {
+        return None;
+    }
+    char::from_u32(code).map(|c| (c, len))
+}
+
+// FORMATに従って引数を書式化する
+// 引数が余っていれば、引数がなくなるまでFORMATを繰り返し適用する
+fn printf(format: &str, args: &[String]) -> MyResult<Vec<u8>> {
+    let mut output = vec![];
+    let mut args = args.iter();
+    loop {
+        let remaining = args.len();
+        if printf_once(format, &mut args, &mut output)? {
+            break;
+        }
+        // 引数を1つも使わないFORMATは1度だけ出力する
+        if args.len() == 0 || args.len() == remaining {
+            break;
+        }
+    }
+    Ok(output)
+}
+
+// FORMATを1度だけ適用する。\cで打ち切った場合はtrueを返す
+fn printf_once(
+    format: &str,
+    args: &mut std::slice::Iter<String>,
+    output: &mut Vec<u8>,
+) -> MyResult<bool> {
+    let mut rest = format;
+    loop {
+        // %までの文字列はエスケープを解釈してそのまま出力する
+        let (literal, spec) = match rest.find('%') {
+            Some(i) => (&rest[..i], Some(&rest[i + 1..])),
+            None => (rest, None),
+        };
+        let (bytes, stopped) = printf_unescape(literal)?;
+        output.extend_from_slice(&bytes);
+        if stopped {
+            return Ok(true);
+        }
+        let Some(spec_str) = spec else {
+            return Ok(false);
+        };
+        let (spec, len) = Spec::parse(spec_str).ok_or_else(|| {
+            // フラグ、幅、精度に続く不正な1文字までをメッセージに含める
+            let end = spec_str
+                .char_indices()
+                .find(|&(_, c)| !"-0+ #.123456789".contains(c))
+                .map_or(spec_str.len(), |(i, c)| i + c.len_utf8());
+            format!("%{}: invalid conversion specification", &spec_str[..end])
+        })?;
+        rest = &spec_str[len..];
+        if spec.conversion == b'%' {
+            output.push(b'%');
+        } else {
+            // 引数が足りない場合は空文字列や0として扱う
+            let arg = args.next().map(String::as_str);
+            output.extend_from_slice(spec.format(arg)?.as_bytes());
+        }
+    }
+}
+
+// %[flags][width][.precision]conversion
+#[derive(Debug, Default, PartialEq)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: u8,
+}
+
+impl Spec {
+    // %の直後からを読み取り、書式と読み取ったバイト数を返す
+    fn parse(s: &str) -> Option<(Spec, usize)> {
+        let bytes = s.as_bytes();
+        let mut spec = Spec::default();
+        let mut i = 0;
+        while let Some(&b) = bytes.get(i) {
+            match b {
+                b'-' => spec.left = true,
+                b'0' => spec.zero = true,
+                b'+' => spec.plus = true,
+                b' ' => spec.space = true,
+                b'#' => spec.alt = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        let (width, len) = parse_digits(&bytes[i..], 10, 9);
+        spec.width = width as usize;
+        i += len;
+        if bytes.get(i) == Some(&b'.') {
+            let (precision, len) = parse_digits(&bytes[i + 1..], 10, 9);
+            spec.precision = Some(precision as usize);
+            i += len + 1;
+        }
+        match bytes.get(i) {
+            Some(&c @ (b's' | b'd' | b'i' | b'x' | b'X' | b'f' | b'%')) => {
+                spec.conversion = c;
+                Some((spec, i + 1))
+            }
+            _ => None,
+        }
+    }
+
+    fn format(&self, arg: Option<&str>) -> MyResult<String> {
+        // 0埋めは符号や0xの後ろに入れるため、本体と分けておく
+        let (prefix, body) = match self.conversion {
+            b's' => {
+                let s = arg.unwrap_or("");
+                let body = match self.precision {
+                    Some(p) => s.chars().take(p).collect(),
+                    None => s.to_string(),
+                };
+                (String::new(), body)
+            }
+            b'd' | b'i' => {
+                let n = parse_int(arg)?;
+                let digits = self.min_digits(n.unsigned_abs().to_string(), n == 0);
+                (self.sign(n < 0).to_string(), digits)
+            }
+            b'x' | b'X' => {
+                // 負の数は2の補数として扱う
+                let n = parse_int(arg)? as u64;
+                let mut digits = self.min_digits(format!("{:x}", n), n == 0);
+                let mut prefix = if self.alt && n != 0 { "0x" } else { "" }.to_string();
+                if self.conversion == b'X' {
+                    digits.make_ascii_uppercase();
+                    prefix.make_ascii_uppercase();
+                }
+                (prefix, digits)
+            }
+            _ => {
+                let v = parse_float(arg)?;
+                let precision = self.precision.unwrap_or(6);
+                let mut body = format!("{:.*}", precision, v.abs());
+                if !v.is_finite() {
+                    body.make_ascii_lowercase();
+                }
+                (self.sign(v.is_sign_negative()).to_string(), body)
+            }
+        };
+
+        let pad = self
+            .width
+            .saturating_sub(prefix.chars().count() + body.chars().count());
+        // 整数は精度を指定すると0埋めしない
+        let zero = self.zero
+            && self.conversion != b's'
+            && (self.conversion == b'f' || self.precision.is_none());
+        Ok(if self.left {
+            format!("{}{}{}", prefix, body, " ".repeat(pad))
+        } else if zero {
+            format!("{}{}{}", prefix, "0".repeat(pad), body)
+        } else {
+            format!("{}{}{}", " ".repeat(pad), prefix, body)
+        })
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    // 精度は整数の最小桁数になる。精度0で値が0なら何も出力しない
+    fn min_digits(&self, digits: String, zero: bool) -> String {
+        match self.precision {
+            Some(0) if zero => String::new(),
+            Some(p) => format!("{:0>1$}", digits, p),
+            None => digits,
+        }
+    }
+}
+
+// 10進数の他、0xで始まれば16進数、0で始まれば8進数、'で始まれば次の文字のコード
+fn parse_int(arg: Option<&str>) -> MyResult<i64> {
+    let s = arg.unwrap_or("").trim_start();
+    if s.is_empty() {
+        return Ok(0);
+    }
+    if let Some(c) = char_value(s) {
+        return Ok(c.into());
+    }
+    let (negative, digits) = match s.as_bytes()[0] {
+        b'-' => (true, &s[1..]),
+        b'+' => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let parsed = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    };
+    // 符号付きの数字以外が含まれていればエラー
+    let n = parsed.map_err(|_| format!("'{}': expected a numeric value", s))?;
+    let n = if negative {
+        0i64.checked_sub_unsigned(n)
+    } else {
+        i64::try_from(n).ok()
+    };
+    Ok(n.ok_or_else(|| format!("'{}': numerical result out of range", s))?)
+}
+
+fn parse_float(arg: Option<&str>) -> MyResult<f64> {
+    let s = arg.unwrap_or("").trim_start();
+    if s.is_empty() {
+        return Ok(0.0);
+    }
+    if let Some(c) = char_value(s) {
+        return Ok(c.into());
+    }
+    Ok(s.parse()
+        .map_err(|_| format!("'{}': expected a numeric value", s))?)
+}
+
+// 'aや"aのように引用符で始まる引数は次の文字のコードを値とする
+fn char_value(s: &str) -> Option<u32> {
+    let rest = s.strip_prefix(['\'', '"'])?;
+    Some(rest.chars().next().map_or(0, u32::from))
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::{parse_digits, parse_int, printf, printf_unescape, unescape, Spec};
+
+    #[test]
+    fn test_parse_digits() {
+        assert_eq!(parse_digits(b"", 8, 3), (0, 0));
+        assert_eq!(parse_digits(b"1018", 8, 3), (0o101, 3));
+        assert_eq!(parse_digits(b"7a", 8, 3), (7, 1));
+        assert_eq!(parse_digits(b"fFz", 16, 2), (0xff, 2));
+        assert_eq!(parse_digits(b"g", 16, 2), (0, 0));
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("plain"), (b"plain".to_vec(), false));
+        assert_eq!(unescape(r"a\tb\nc\\"), (b"a\tb\nc\\".to_vec(), false));
+        assert_eq!(unescape(r"end\"), (b"end\\".to_vec(), false));
+        assert_eq!(unescape(r"\q\1"), (br"\q\1".to_vec(), false));
+
+        // \cより後は出力しない
+        assert_eq!(unescape(r"a\cb\n"), (b"a".to_vec(), true));
+
+        // 8進数と16進数
+        assert_eq!(unescape(r"\0101\0"), (b"A\0".to_vec(), false));
+        assert_eq!(unescape(r"\0400"), (b"\0".to_vec(), false));
+        assert_eq!(
+            unescape(r"\x41\x4g\xff\x"),
+            (b"A\x04g\xff\\x".to_vec(), false)
+        );
+
+        // Unicode
+        assert_eq!(
+            unescape(r"\u00e9\U0001F600\u{1F980}"),
+            ("é😀🦀".as_bytes().to_vec(), false)
+        );
+        assert_eq!(unescape(r"\u{41"), (br"\u{41".to_vec(), false));
+        assert_eq!(unescape(r"\u{}"), (br"\u{}".to_vec(), false));
+        assert_eq!(unescape(r"\u{110000}"), (br"\u{110000}".to_vec(), false));
+        assert_eq!(unescape(r"\uD800"), (br"\uD800".to_vec(), false));
+        assert_eq!(unescape(r"\uzz"), (br"\uzz".to_vec(), false));
+    }
+
+    #[test]
+    fn test_parse_spec() {
+        assert_eq!(
+            Spec::parse("s"),
+            Some((
+                Spec {
+                    conversion: b's',
+                    ..Spec::default()
+                },
+                1
+            ))
+        );
+        assert_eq!(
+            Spec::parse("-08.3frest"),
+            Some((
+                Spec {
+                    left: true,
+                    zero: true,
+                    width: 8,
+                    precision: Some(3),
+                    conversion: b'f',
+                    ..Spec::default()
+                },
+                6
+            ))
+        );
+        assert_eq!(
+            Spec::parse(".x").map(|(spec, _)| spec.precision),
+            Some(Some(0))
+        );
+        assert_eq!(Spec::parse(""), None);
+        assert_eq!(Spec::parse("5q"), None);
+    }
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int(None).unwrap(), 0);
+        assert_eq!(parse_int(Some("")).unwrap(), 0);
+        assert_eq!(parse_int(Some(" 42")).unwrap(), 42);
+        assert_eq!(parse_int(Some("-42")).unwrap(), -42);
+        assert_eq!(parse_int(Some("+0x1F")).unwrap(), 31);
+        assert_eq!(parse_int(Some("010")).unwrap(), 8);
+        assert_eq!(parse_int(Some("'A")).unwrap(), 65);
+        assert_eq!(parse_int(Some("-9223372036854775808")).unwrap(), i64::MIN);
+
+        let res = parse_int(Some("abc"));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'abc': expected a numeric value"
+        );
+        let res = parse_int(Some("12abc"));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'12abc': expected a numeric value"
+        );
+        let res = parse_int(Some("9223372036854775808"));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "'9223372036854775808': numerical result out of range"
+        );
+    }
+
+    #[test]
+    fn test_printf() {
+        let run = |format: &str, args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+            String::from_utf8(printf(format, &args).unwrap()).unwrap()
+        };
+        assert_eq!(
+            run("[%5s|%-5s|%.2s]", &["ab", "cd", "efg"]),
+            "[   ab|cd   |ef]"
+        );
+        assert_eq!(
+            run("[%5d|%-5d|%05d|%+d|% d]", &["1", "2", "-3", "4", "5"]),
+            "[    1|2    |-0003|+4| 5]"
+        );
+        assert_eq!(
+            run("[%.3d|%06.3d|%.0d]", &["7", "-7", "0"]),
+            "[007|  -007|]"
+        );
+        assert_eq!(
+            run("[%x|%X|%#x|%#06x]", &["255", "255", "255", "255"]),
+            "[ff|FF|0xff|0x00ff]"
+        );
+        assert_eq!(run("[%x]", &["-1"]), "[ffffffffffffffff]");
+        assert_eq!(
+            run(
+                "[%f|%.2f|%08.3f|%-7.1f]",
+                &["1.5", "2.345", "-3.14159", "4"]
+            ),
+            "[1.500000|2.35|-003.142|4.0    ]"
+        );
+        assert_eq!(run("[%f|%f]", &["inf", "-0"]), "[inf|-0.000000]");
+        assert_eq!(run("100%%\n", &[]), "100%\n");
+
+        // 引数が足りなければ空文字列や0、余れば書式を繰り返す
+        assert_eq!(run("%s=%d;", &["a", "1", "b"]), "a=1;b=0;");
+        assert_eq!(run("once\n", &["unused"]), "once\n");
+        assert_eq!(run("%s\\c%s", &["a", "b"]), "a");
+    }
+
+    #[test]
+    fn test_printf_unescape() {
+        let ok = |text: &str| printf_unescape(text).unwrap();
+        assert_eq!(ok(r"a\tb\nc\\\q"), (b"a\tb\nc\\\\q".to_vec(), false));
+        assert_eq!(ok(r#"\"end\"#), (b"\"end\\".to_vec(), false));
+        assert_eq!(ok(r"a\cb"), (b"a".to_vec(), true));
 
-    let ending = if omit_newline { "" } else { "\n" };
+        // \NNNは\0を付けず1〜3桁
+        assert_eq!(
+            ok(r"\101\0101\1010\7\8"),
+            (b"A\x081A0\x07\\8".to_vec(), false)
+        );
+        assert_eq!(ok(r"\777"), (b"\xff".to_vec(), false));
+        assert_eq!(ok(r"\x41\x4g"), (b"A\x04g".to_vec(), false));
+        assert_eq!(ok(r"é\U0001F600"), ("é😀".as_bytes().to_vec(), false));
 
-    print!("{}{}", text.join(" "), ending);
+        let err = |text: &str| printf_unescape(text).unwrap_err().to_string();
+        assert_eq!(err(r"\x"), "missing hexadecimal number in escape");
+        assert_eq!(err(r"\u41"), "missing hexadecimal number in escape");
+        assert_eq!(err(r"\u{41}"), "missing hexadecimal number in escape");
+        assert_eq!(err(r"\U0001F60"), "missing hexadecimal number in escape");
+        assert_eq!(err(r"\uD800"), r"invalid universal character name \uD800");
+        assert_eq!(
+            err(r"\U00110000"),
+            r"invalid universal character name \U00110000"
+        );
+    }
 }
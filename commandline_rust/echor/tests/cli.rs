@@ -15,7 +15,7 @@ fn dies_no_args() -> TestResult {
 
 // ヘルパー関数
 fn run(args: &[&str], expected_file: &str) -> TestResult {
-    let expected = fs::read_to_string(expected_file)?;
+    let expected = fs::read(expected_file)?;
     Command::cargo_bin("echor")?
         .args(args)
         .assert()
@@ -43,3 +43,130 @@ fn hello1_no_newline() -> TestResult {
 fn hello2_no_newline() -> TestResult {
     run(&["-n", "Hello", "there"], "tests/expected/hello2.n.txt")
 }
+
+#[test]
+fn escapes() -> TestResult {
+    let args = [r"Hello\tthere\\", r"new\nline", r"unknown\q"];
+    run(
+        &[&["-e"], &args[..]].concat(),
+        "tests/expected/escapes.e.txt",
+    )?;
+    run(
+        &[&["-E"], &args[..]].concat(),
+        "tests/expected/escapes.E.txt",
+    )?;
+    run(&args, "tests/expected/escapes.E.txt")
+}
+
+#[test]
+fn escapes_last_flag_wins() -> TestResult {
+    let args = [r"Hello\tthere\\", r"new\nline", r"unknown\q"];
+    run(
+        &[&["-E", "-e"], &args[..]].concat(),
+        "tests/expected/escapes.e.txt",
+    )?;
+    run(
+        &[&["-e", "-E"], &args[..]].concat(),
+        "tests/expected/escapes.E.txt",
+    )
+}
+
+#[test]
+fn escapes_stop() -> TestResult {
+    // \c以降は改行も含めて出力しない
+    run(&["-e", r"Hello\c", "there"], "tests/expected/stop.e.txt")
+}
+
+#[test]
+fn escapes_bytes() -> TestResult {
+    run(
+        &["-e", r"\0101\x42\x4g\0\xff\x\0777"],
+        "tests/expected/bytes.e.txt",
+    )
+}
+
+#[test]
+fn escapes_unicode() -> TestResult {
+    run(
+        &["-e", r"\u00e9\U0001F600\u{1F980}\uzz\u{}"],
+        "tests/expected/unicode.e.txt",
+    )
+}
+
+#[test]
+fn printf() -> TestResult {
+    run(
+        &[
+            "--printf",
+            r"%-6s|%5d|%#x|%08.3f\n",
+            "name",
+            "42",
+            "255",
+            "-3.14159",
+        ],
+        "tests/expected/printf.txt",
+    )
+}
+
+#[test]
+fn printf_reuse() -> TestResult {
+    // 引数が余れば書式を繰り返し、足りない分は空文字列や0になる
+    run(
+        &["--printf", r"%s=%03d\n", "a", "1", "b", "2", "c"],
+        "tests/expected/printf.reuse.txt",
+    )
+}
+
+#[test]
+fn printf_no_args() -> TestResult {
+    Command::cargo_bin("echor")?
+        .args(["--printf", r"100%%\n"])
+        .assert()
+        .success()
+        .stdout("100%\n");
+    Ok(())
+}
+
+#[test]
+fn dies_printf_not_numeric() -> TestResult {
+    Command::cargo_bin("echor")?
+        .args(["--printf", "%d %f", "1", "abc"])
+        .assert()
+        .failure()
+        .stderr("'abc': expected a numeric value\n");
+    Ok(())
+}
+
+#[test]
+fn dies_printf_bad_conversion() -> TestResult {
+    Command::cargo_bin("echor")?
+        .args(["--printf", "%5q", "1"])
+        .assert()
+        .failure()
+        .stderr("%5q: invalid conversion specification\n");
+    Ok(())
+}
+
+#[test]
+fn printf_escapes() -> TestResult {
+    // FORMATのエスケープはecho -eではなくprintf(1)に従う
+    run(
+        &[
+            "--printf",
+            r#"\101\0101\1010\x41\x4g\"\q\\%s\n"#,
+            "one",
+            "two",
+        ],
+        "tests/expected/printf.escapes.txt",
+    )
+}
+
+#[test]
+fn dies_printf_bad_escape() -> TestResult {
+    Command::cargo_bin("echor")?
+        .args(["--printf", r"\x"])
+        .assert()
+        .failure()
+        .stderr("missing hexadecimal number in escape\n");
+    Ok(())
+}
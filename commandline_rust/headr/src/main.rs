@@ -1,7 +1,12 @@
 use anyhow::Result;
 use clap::Parser;
+use regex::bytes::Regex;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::str;
+use std::thread;
+use std::time::Duration;
+use unicode_segmentation::GraphemeCursor;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -14,7 +19,7 @@ struct Args {
         long,
         default_value = "10",
         value_name = "LINES",
-        value_parser = clap::value_parser!(u64).range(1..)
+        value_parser = parse_size
     )]
     lines: u64,
 
@@ -23,11 +28,54 @@ struct Args {
         long,
         value_name = "BYTES",
         conflicts_with("lines"),
-        value_parser = clap::value_parser!(u64).range(1..)
+        value_parser = parse_size
     )]
     bytes: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "CHARS",
+        conflicts_with_all(["lines", "bytes"]),
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    chars: Option<u64>,
+
+    #[arg(long, requires("chars"))]
+    graphemes: bool,
+
+    #[arg(short, long, alias("silent"), overrides_with("verbose"))]
+    quiet: bool,
+
+    #[arg(short, long, overrides_with("quiet"))]
+    verbose: bool,
+
+    #[arg(short, long("zero-terminated"))]
+    zero: bool,
+
+    #[arg(
+        long,
+        value_name = "REGEX",
+        conflicts_with_all(["bytes", "chars"]),
+        value_parser(Regex::new)
+    )]
+    grep: Option<Regex>,
+
+    #[arg(short, long, conflicts_with_all(["bytes", "chars"]))]
+    follow: bool,
+
+    #[arg(
+        long,
+        value_name = "NUM",
+        default_value = "0",
+        conflicts_with("chars"),
+        value_parser = parse_number
+    )]
+    skip: u64,
 }
 
+// --followでファイル末尾に達したときに次の読み込みまで待つ時間
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(100);
+
 fn main() {
     if let Err(e) = run(Args::parse()) {
         eprintln!("{}", e);
@@ -37,11 +85,15 @@ fn main() {
 
 fn run(args: Args) -> Result<()> {
     let num_files = args.files.len();
+    let print_header = args.verbose || (!args.quiet && num_files > 1);
+    let delimiter = if args.zero { b'\0' } else { b'\n' };
     for (file_num, filename) in args.files.iter().enumerate() {
-        match open(&filename) {
+        // バイト単位の場合は読み込む前に読み飛ばしておく
+        let offset = if args.bytes.is_some() { args.skip } else { 0 };
+        match open(filename, offset) {
             Err(e) => eprintln!("{}: {}", filename, e),
-            Ok(mut file) => {
-                if num_files > 1 {
+            Ok(file) => {
+                if print_header {
                     println!(
                         "{}==> {} <==",
                         if file_num > 0 { "\n" } else { "" },
@@ -50,19 +102,22 @@ fn run(args: Args) -> Result<()> {
                 }
 
                 if let Some(bytes) = args.bytes {
-                    let mut buf = vec![0u8; bytes as usize];
-                    let bytes_read = file.read(&mut buf)?;
-                    print!("{}", String::from_utf8_lossy(&buf[..bytes_read]));
+                    // パイプからの読み込みは1回のreadでは足りないことがあるので、
+                    // N bytesかEOFまで読み込んでそのまま書き出す
+                    io::copy(&mut file.take(bytes), &mut io::stdout())?;
+                } else if let Some(chars) = args.chars {
+                    print_chars(file, chars, args.graphemes)?;
                 } else {
-                    let mut buf = String::new();
-                    for _ in 0..args.lines {
-                        let bytes = file.read_line(&mut buf)?;
-                        if bytes == 0 {
-                            break;
-                        }
-                        print!("{}", buf);
-                        buf.clear();
-                    }
+                    // 標準入力はEOFに達したらそれ以上読めないのでfollowしない
+                    let follow = args.follow && filename != "-";
+                    print_lines(
+                        file,
+                        args.skip,
+                        args.lines,
+                        delimiter,
+                        args.grep.as_ref(),
+                        follow,
+                    )?;
                 }
             }
         }
@@ -70,9 +125,275 @@ fn run(args: Args) -> Result<()> {
     Ok(())
 }
 
-fn open(filename: &str) -> Result<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+fn print_lines(
+    mut file: impl BufRead,
+    skip: u64,
+    num_lines: u64,
+    delimiter: u8,
+    pattern: Option<&Regex>,
+    follow: bool,
+) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    let mut buf = Vec::new();
+    let mut skipped = 0;
+    let mut printed = 0;
+    while printed < num_lines {
+        let bytes = file.read_until(delimiter, &mut buf)?;
+        if follow && buf.last() != Some(&delimiter) {
+            // 書き込み途中の行は区切り文字が来るまで待つ
+            if bytes == 0 {
+                thread::sleep(FOLLOW_INTERVAL);
+            }
+            continue;
+        }
+        if buf.is_empty() {
+            break;
+        }
+        if skipped < skip {
+            skipped += 1;
+            buf.clear();
+            continue;
+        }
+
+        let record = buf.strip_suffix(&[delimiter]).unwrap_or(&buf);
+        if pattern.is_none_or(|re| re.is_match(record)) {
+            stdout.write_all(&buf)?;
+            // パイプの先で結果をすぐに受け取れるように1行ずつflushする
+            if pattern.is_some() || follow {
+                stdout.flush()?;
+            }
+            printed += 1;
+        }
+        buf.clear();
+    }
+    Ok(())
+}
+
+// "1K", "2MiB", "1e3"のような単位付きの値を解釈する
+// エラーメッセージはclapのrange指定時のものに合わせる
+fn parse_size(value: &str) -> Result<u64, String> {
+    let size = parse_number(value)?;
+    if size == 0 {
+        return Err(format!("{} is not in 1..{}", size, u64::MAX));
+    }
+    Ok(size)
+}
+
+// parse_sizeと同じ単位を受け付けるが、0も許す
+fn parse_number(value: &str) -> Result<u64, String> {
+    let pos = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (num, suffix) = value.split_at(pos);
+
+    let multiplier = match suffix {
+        "" => 1,
+        "b" => 512,
+        "kB" | "KB" => 1000,
+        "K" | "KiB" => 1 << 10,
+        "MB" => 1000u64.pow(2),
+        "M" | "MiB" => 1 << 20,
+        "GB" => 1000u64.pow(3),
+        "G" | "GiB" => 1 << 30,
+        "TB" => 1000u64.pow(4),
+        "T" | "TiB" => 1 << 40,
+        "PB" => 1000u64.pow(5),
+        "P" | "PiB" => 1 << 50,
+        "EB" => 1000u64.pow(6),
+        "E" | "EiB" => 1 << 60,
+        _ => match suffix.strip_prefix('e').map(str::parse::<u32>) {
+            Some(Ok(exp)) => 10u64
+                .checked_pow(exp)
+                .ok_or("number too large to fit in target type")?,
+            // 解釈できない単位はu64としてのパースエラーをそのまま返す
+            _ => return value.parse::<u64>().map_err(|e| e.to_string()),
+        },
+    };
+
+    num.parse::<u64>()
+        .map_err(|e| e.to_string())?
+        .checked_mul(multiplier)
+        .ok_or_else(|| "number too large to fit in target type".to_string())
+}
+
+// 1文字ずつ読みながら出力し、N文字に達したらそれ以上読まない
+fn print_chars(mut file: impl BufRead, num_chars: u64, graphemes: bool) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    let mut remaining = num_chars;
+    // 書記素クラスタの場合は、次の文字を読むまで終わりの分からないクラスタだけを持つ
+    let mut pending = String::new();
+    let mut bytes = [0; 4];
+    while remaining > 0 {
+        let len = read_char(&mut file, &mut bytes)?;
+        let valid = str::from_utf8(&bytes[..len]).ok().filter(|c| !c.is_empty());
+        if graphemes {
+            if let Some(c) = valid {
+                let start = pending.len();
+                pending.push_str(c);
+                // 読んだ文字から新しいクラスタが始まるなら、それまでのクラスタは完結している
+                let mut cursor = GraphemeCursor::new(start, pending.len(), true);
+                if start > 0 && cursor.is_boundary(&pending, 0).unwrap_or(true) {
+                    stdout.write_all(&pending.as_bytes()[..start])?;
+                    pending.drain(..start);
+                    remaining -= 1;
+                }
+                continue;
+            }
+            // EOFか不正なバイト列でもクラスタは終わる
+            if !pending.is_empty() {
+                stdout.write_all(pending.as_bytes())?;
+                pending.clear();
+                remaining -= 1;
+                if remaining == 0 {
+                    break;
+                }
+            }
+        }
+        if len == 0 {
+            break;
+        }
+        stdout.write_all(&bytes[..len])?;
+        remaining -= 1;
+    }
+    Ok(())
+}
+
+// 1文字(Unicodeスカラー値)を読んでbytesに入れ、バイト数を返す。EOFなら0を返す
+// 不正なUTF-8はstr::Utf8Chunksと同じく、正しい並びになり得る最長の部分を1文字として数える
+fn read_char(file: &mut impl BufRead, bytes: &mut [u8; 4]) -> io::Result<usize> {
+    let mut len = 0;
+    while let Some(&b) = file.fill_buf()?.first() {
+        if len > 0 && !is_continuation(bytes[0], len, b) {
+            break;
+        }
+        bytes[len] = b;
+        len += 1;
+        file.consume(1);
+        if len == utf8_width(bytes[0]) {
+            break;
+        }
+    }
+    Ok(len)
+}
+
+// 先頭のバイトから分かる1文字のバイト数。先頭になり得ないバイトは1とする
+fn utf8_width(lead: u8) -> usize {
+    match lead {
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => 1,
+    }
+}
+
+// leadで始まる文字のpos番目のバイトとしてbが正しいか
+// 2バイト目は冗長な表現、サロゲート、U+10FFFFを超える値にならない範囲に限る
+fn is_continuation(lead: u8, pos: usize, b: u8) -> bool {
+    let range = match (lead, pos) {
+        (0xe0, 1) => 0xa0..=0xbf,
+        (0xed, 1) => 0x80..=0x9f,
+        (0xf0, 1) => 0x90..=0xbf,
+        (0xf4, 1) => 0x80..=0x8f,
+        _ => 0x80..=0xbf,
+    };
+    range.contains(&b)
+}
+
+// offsetバイト読み飛ばした状態で開く
+// 通常のファイルはseekし、それ以外は読み捨てる
+fn open(filename: &str, offset: u64) -> Result<Box<dyn BufRead>> {
+    let mut file: Box<dyn BufRead> = match filename {
+        "-" => Box::new(BufReader::new(io::stdin())),
+        _ => {
+            let mut file = File::open(filename)?;
+            if offset > 0 && file.metadata()?.is_file() {
+                file.seek(SeekFrom::Start(offset))?;
+                return Ok(Box::new(BufReader::new(file)));
+            }
+            Box::new(BufReader::new(file))
+        }
+    };
+    io::copy(&mut file.by_ref().take(offset), &mut io::sink())?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::{parse_number, parse_size};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_size() {
+        // Plain integers
+        assert_eq!(parse_size("1"), Ok(1));
+        assert_eq!(parse_size("512"), Ok(512));
+
+        // Binary and decimal suffixes
+        assert_eq!(parse_size("1b"), Ok(512));
+        assert_eq!(parse_size("1K"), Ok(1024));
+        assert_eq!(parse_size("1KiB"), Ok(1024));
+        assert_eq!(parse_size("1kB"), Ok(1000));
+        assert_eq!(parse_size("1KB"), Ok(1000));
+        assert_eq!(parse_size("2M"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_size("2MiB"), Ok(2 * 1024 * 1024));
+        assert_eq!(parse_size("2MB"), Ok(2_000_000));
+        assert_eq!(parse_size("3G"), Ok(3 << 30));
+        assert_eq!(parse_size("3GB"), Ok(3_000_000_000));
+        assert_eq!(parse_size("1E"), Ok(1 << 60));
+
+        // Exponents
+        assert_eq!(parse_size("1e3"), Ok(1000));
+        assert_eq!(parse_size("25e0"), Ok(25));
+
+        // Zero is an error
+        assert_eq!(
+            parse_size("0"),
+            Err("0 is not in 1..18446744073709551615".to_string())
+        );
+        assert_eq!(
+            parse_size("0K"),
+            Err("0 is not in 1..18446744073709551615".to_string())
+        );
+
+        // Unknown suffixes and non-numbers are errors
+        assert_eq!(
+            parse_size("1X"),
+            Err("invalid digit found in string".to_string())
+        );
+        assert_eq!(
+            parse_size("1e"),
+            Err("invalid digit found in string".to_string())
+        );
+        assert_eq!(
+            parse_size("K"),
+            Err("cannot parse integer from empty string".to_string())
+        );
+        assert_eq!(
+            parse_size("-1"),
+            Err("invalid digit found in string".to_string())
+        );
+
+        // Overflow is an error
+        assert_eq!(
+            parse_size("16E"),
+            Err("number too large to fit in target type".to_string())
+        );
+        assert_eq!(
+            parse_size("1e20"),
+            Err("number too large to fit in target type".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("0"), Ok(0));
+        assert_eq!(parse_number("0K"), Ok(0));
+        assert_eq!(parse_number("999"), Ok(999));
+        assert_eq!(parse_number("1K"), Ok(1024));
+        assert_eq!(parse_number("1e3"), Ok(1000));
+        assert_eq!(
+            parse_number("a"),
+            Err("invalid digit found in string".to_string())
+        );
     }
 }
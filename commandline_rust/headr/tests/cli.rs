@@ -1,10 +1,14 @@
 use anyhow::Result;
-use assert_cmd::Command;
+use assert_cmd::{cargo::cargo_bin, Command};
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
-use std::fs::{self, File};
+use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
+use std::process::{self, Child, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::NamedTempFile;
 
 const PRG: &str = "headr";
 const EMPTY: &str = "./tests/inputs/empty.txt";
@@ -12,6 +16,9 @@ const ONE: &str = "./tests/inputs/one.txt";
 const TWO: &str = "./tests/inputs/two.txt";
 const THREE: &str = "./tests/inputs/three.txt";
 const TWELVE: &str = "./tests/inputs/twelve.txt";
+const LATIN1: &str = "./tests/inputs/latin1.txt";
+const GRAPHEMES: &str = "./tests/inputs/graphemes.txt";
+const ZERO: &str = "./tests/inputs/zero.txt";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -67,6 +74,34 @@ fn dies_bad_lines() -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_bad_suffix() -> Result<()> {
+    let expected = "error: invalid value '1X' for \
+        '--bytes <BYTES>': invalid digit found in string";
+    Command::cargo_bin(PRG)?
+        .args(["-c", "1X", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(expected));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_zero_lines() -> Result<()> {
+    let expected = "error: invalid value '0K' for \
+        '--lines <LINES>': 0 is not in 1..18446744073709551615";
+    Command::cargo_bin(PRG)?
+        .args(["-n", "0K", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(expected));
+
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn dies_bytes_and_lines() -> Result<()> {
@@ -82,6 +117,33 @@ fn dies_bytes_and_lines() -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_chars_and_bytes() -> Result<()> {
+    let msg = "the argument '--chars <CHARS>' cannot be \
+               used with '--bytes <BYTES>'";
+
+    Command::cargo_bin(PRG)?
+        .args(["--chars", "1", "-c", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(msg));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_graphemes_without_chars() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--graphemes", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--chars <CHARS>"));
+
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn skips_bad_file() -> Result<()> {
@@ -134,6 +196,35 @@ fn run_stdin(
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_raw(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(PRG)?.args(args).output().expect("fail");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_stdin_raw(
+    args: &[&str],
+    input_file: &str,
+    expected_file: &str,
+) -> Result<()> {
+    let expected = fs::read(expected_file)?;
+    let input = fs::read(input_file)?;
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .args(args)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> Result<()> {
@@ -423,3 +514,363 @@ fn multiple_files_c4() -> Result<()> {
         "tests/expected/all.c4.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn twelve_n1e1() -> Result<()> {
+    run(&[TWELVE, "-n", "1e1"], "tests/expected/twelve.txt.out")
+}
+
+#[test]
+fn twelve_c1k() -> Result<()> {
+    run_raw(&[TWELVE, "-c", "1K"], TWELVE)
+}
+
+#[test]
+fn twelve_c1kib_stdin() -> Result<()> {
+    run_stdin_raw(&["-c", "1KiB"], TWELVE, TWELVE)
+}
+
+// --------------------------------------------------
+#[test]
+fn one_c1_raw() -> Result<()> {
+    run_raw(&[ONE, "-c", "1"], "tests/expected/one.txt.c1.out")
+}
+
+#[test]
+fn one_c1_stdin_raw() -> Result<()> {
+    run_stdin_raw(&["-c", "1"], ONE, "tests/expected/one.txt.c1.out")
+}
+
+#[test]
+fn latin1_c4() -> Result<()> {
+    run_raw(&[LATIN1, "-c", "4"], "tests/expected/latin1.txt.c4.out")
+}
+
+#[test]
+fn latin1_c4_stdin() -> Result<()> {
+    run_stdin_raw(&["-c", "4"], LATIN1, "tests/expected/latin1.txt.c4.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_chars2() -> Result<()> {
+    run_raw(&[EMPTY, "--chars", "2"], "tests/expected/empty.txt.c2.out")
+}
+
+#[test]
+fn one_chars2() -> Result<()> {
+    run_raw(&[ONE, "--chars", "2"], "tests/expected/one.txt.chars2.out")
+}
+
+#[test]
+fn one_chars2_stdin() -> Result<()> {
+    run_stdin_raw(&["--chars", "2"], ONE, "tests/expected/one.txt.chars2.out")
+}
+
+#[test]
+fn latin1_chars4() -> Result<()> {
+    run_raw(
+        &[LATIN1, "--chars", "4"],
+        "tests/expected/latin1.txt.chars4.out",
+    )
+}
+
+#[test]
+fn graphemes_chars4() -> Result<()> {
+    run_raw(
+        &[GRAPHEMES, "--chars", "4"],
+        "tests/expected/graphemes.txt.chars4.out",
+    )
+}
+
+#[test]
+fn graphemes_chars4_graphemes() -> Result<()> {
+    run_raw(
+        &[GRAPHEMES, "--chars", "4", "--graphemes"],
+        "tests/expected/graphemes.txt.chars4.g.out",
+    )
+}
+
+#[test]
+fn graphemes_chars16_graphemes() -> Result<()> {
+    run_raw(
+        &[GRAPHEMES, "--chars", "16", "--graphemes"],
+        "tests/expected/graphemes.txt.chars16.g.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn one_verbose() -> Result<()> {
+    run(&["-v", ONE], "tests/expected/one.txt.v.out")
+}
+
+#[test]
+fn multiple_files_quiet() -> Result<()> {
+    run(
+        &["--quiet", EMPTY, ONE, TWO, THREE, TWELVE],
+        "tests/expected/all.q.out",
+    )
+}
+
+#[test]
+fn multiple_files_verbose_quiet() -> Result<()> {
+    run(&["-v", "-q", EMPTY, ONE], "tests/expected/all.vq.out")
+}
+
+#[test]
+fn multiple_files_quiet_verbose() -> Result<()> {
+    run(&["-q", "-v", EMPTY, ONE], "tests/expected/all.qv.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_n2() -> Result<()> {
+    run_raw(&[ZERO, "-n", "2"], "tests/expected/zero.txt.n2.out")
+}
+
+#[test]
+fn zero_z_n2() -> Result<()> {
+    run_raw(&[ZERO, "-z", "-n", "2"], "tests/expected/zero.txt.z.n2.out")
+}
+
+#[test]
+fn zero_z_n2_stdin() -> Result<()> {
+    run_stdin_raw(
+        &["--zero-terminated", "-n", "2"],
+        ZERO,
+        "tests/expected/zero.txt.z.n2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_grep_and_bytes() -> Result<()> {
+    let msg = "the argument '--grep <REGEX>' cannot be \
+               used with '--bytes <BYTES>'";
+
+    Command::cargo_bin(PRG)?
+        .args(["--grep", "e", "-c", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(msg));
+
+    Ok(())
+}
+
+#[test]
+fn dies_bad_grep() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--grep", "*", ONE])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value '*' for '--grep <REGEX>'",
+        ));
+
+    Ok(())
+}
+
+#[test]
+fn twelve_grep_n3() -> Result<()> {
+    run(
+        &[TWELVE, "--grep", "e", "-n", "3"],
+        "tests/expected/twelve.txt.grep_e.n3.out",
+    )
+}
+
+#[test]
+fn twelve_grep_n3_stdin() -> Result<()> {
+    run_stdin(
+        &["--grep", "e", "-n", "3"],
+        TWELVE,
+        "tests/expected/twelve.txt.grep_e.n3.out",
+    )
+}
+
+#[test]
+fn twelve_grep_anchored() -> Result<()> {
+    run(
+        &[TWELVE, "--grep", "^t"],
+        "tests/expected/twelve.txt.grep_t.out",
+    )
+}
+
+// --------------------------------------------------
+// 子プロセスが終了するまで待つ。時間内に終了しなければkillして失敗にする
+fn wait_exit(child: &mut Child) -> Result<Vec<u8>> {
+    let start = Instant::now();
+    while child.try_wait()?.is_none() {
+        if start.elapsed() > Duration::from_secs(10) {
+            child.kill()?;
+            anyhow::bail!("{PRG} did not exit");
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    let mut stdout = vec![];
+    child.stdout.take().unwrap().read_to_end(&mut stdout)?;
+    Ok(stdout)
+}
+
+#[test]
+fn grep_stops_at_nth_match_stdin() -> Result<()> {
+    let mut child = process::Command::new(cargo_bin(PRG))
+        .args(["--grep", "ERROR", "-n", "1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // 標準入力を閉じずに、マッチした行を書いた時点で終了することを確認する
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"INFO start\nERROR boom\n")?;
+    stdin.flush()?;
+
+    let stdout = wait_exit(&mut child)?;
+    assert_eq!(String::from_utf8(stdout)?, "ERROR boom\n");
+    drop(stdin);
+
+    Ok(())
+}
+
+#[test]
+fn chars_stops_before_end_of_line_stdin() -> Result<()> {
+    let mut child = process::Command::new(cargo_bin(PRG))
+        .args(["--chars", "3", "--graphemes"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // 改行を書かずに標準入力を開いたままでも、N文字を読んだ時点で終了することを確認する
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all("ae\u{301}o\u{308}x".as_bytes())?;
+    stdin.flush()?;
+
+    let stdout = wait_exit(&mut child)?;
+    assert_eq!(String::from_utf8(stdout)?, "ae\u{301}o\u{308}");
+    drop(stdin);
+
+    Ok(())
+}
+
+#[test]
+fn follow_waits_for_matches() -> Result<()> {
+    let mut log = NamedTempFile::new()?;
+    writeln!(log, "INFO start")?;
+    writeln!(log, "ERROR one")?;
+
+    let mut child = process::Command::new(cargo_bin(PRG))
+        .args(["-f", "--grep", "ERROR", "-n", "2"])
+        .arg(log.path())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // 2つ目のマッチは後から書き足され、途中までの行も待って読まれる
+    thread::sleep(Duration::from_millis(300));
+    let mut file = OpenOptions::new().append(true).open(log.path())?;
+    write!(file, "INFO waiting\nERR")?;
+    file.flush()?;
+    thread::sleep(Duration::from_millis(300));
+    writeln!(file, "OR two")?;
+
+    let stdout = wait_exit(&mut child)?;
+    assert_eq!(String::from_utf8(stdout)?, "ERROR one\nERROR two\n");
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_skip_and_chars() -> Result<()> {
+    let msg = "the argument '--skip <NUM>' cannot be \
+               used with '--chars <CHARS>'";
+
+    Command::cargo_bin(PRG)?
+        .args(["--skip", "1", "--chars", "2"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(msg));
+
+    Ok(())
+}
+
+#[test]
+fn twelve_skip0() -> Result<()> {
+    run(&[TWELVE, "--skip", "0"], "tests/expected/twelve.txt.out")
+}
+
+#[test]
+fn twelve_skip2_n3() -> Result<()> {
+    run(
+        &[TWELVE, "--skip", "2", "-n", "3"],
+        "tests/expected/twelve.txt.skip2.n3.out",
+    )
+}
+
+#[test]
+fn twelve_skip2_n3_stdin() -> Result<()> {
+    run_stdin(
+        &["--skip", "2", "-n", "3"],
+        TWELVE,
+        "tests/expected/twelve.txt.skip2.n3.out",
+    )
+}
+
+#[test]
+fn twelve_skip10() -> Result<()> {
+    run(
+        &[TWELVE, "--skip", "10"],
+        "tests/expected/twelve.txt.skip10.out",
+    )
+}
+
+#[test]
+fn twelve_skip_past_end() -> Result<()> {
+    run(&[TWELVE, "--skip", "1K"], "tests/expected/empty.txt.out")
+}
+
+#[test]
+fn twelve_skip4_grep_n2() -> Result<()> {
+    run(
+        &[TWELVE, "--skip", "4", "--grep", "e", "-n", "2"],
+        "tests/expected/twelve.txt.skip4.grep_e.n2.out",
+    )
+}
+
+#[test]
+fn zero_z_skip2_n1() -> Result<()> {
+    run_raw(
+        &[ZERO, "-z", "--skip", "2", "-n", "1"],
+        "tests/expected/zero.txt.z.skip2.n1.out",
+    )
+}
+
+#[test]
+fn twelve_skip3_c5() -> Result<()> {
+    run_raw(
+        &[TWELVE, "--skip", "3", "-c", "5"],
+        "tests/expected/twelve.txt.skip3.c5.out",
+    )
+}
+
+#[test]
+fn twelve_skip3_c5_stdin() -> Result<()> {
+    run_stdin_raw(
+        &["--skip", "3", "-c", "5"],
+        TWELVE,
+        "tests/expected/twelve.txt.skip3.c5.out",
+    )
+}
+
+#[test]
+fn twelve_skip_past_end_c5() -> Result<()> {
+    run_raw(&[TWELVE, "--skip", "1K", "-c", "5"], EMPTY)
+}
+
+#[test]
+fn one_skip2_c2() -> Result<()> {
+    run_raw(
+        &[ONE, "--skip", "2", "-c", "2"],
+        "tests/expected/one.txt.skip2.c2.out",
+    )
+}
@@ -12,6 +12,7 @@ echo -ne "a\nb"      > $ROOT/t3.txt
 echo -ne "a\na\nb"   > $ROOT/t4.txt
 echo -ne "b\na\na\n" > $ROOT/t5.txt
 echo -ne "a\nb\nc\n" > $ROOT/t6.txt
+echo -ne "a\na\nb\nc\nc\nc\nd\n" > $ROOT/t7.txt
 
 for FILE in $ROOT/*.txt; do
     BASENAME=$(basename "$FILE")
@@ -20,3 +21,72 @@ for FILE in $ROOT/*.txt; do
     uniq    < $FILE > ${OUT_DIR}/${BASENAME}.stdin.out
     uniq -c < $FILE > ${OUT_DIR}/${BASENAME}.stdin.c.out
 done
+
+for FILE in $ROOT/t*.txt; do
+    BASENAME=$(basename "$FILE")
+    uniq -d                        $FILE > ${OUT_DIR}/${BASENAME}.d.out
+    uniq -d -c                     $FILE > ${OUT_DIR}/${BASENAME}.d.c.out
+    uniq -u                        $FILE > ${OUT_DIR}/${BASENAME}.u.out
+    uniq -u -c                     $FILE > ${OUT_DIR}/${BASENAME}.u.c.out
+    uniq -D                        $FILE > ${OUT_DIR}/${BASENAME}.D.out
+    uniq --all-repeated=separate   $FILE > ${OUT_DIR}/${BASENAME}.D.separate.out
+    uniq --all-repeated=prepend    $FILE > ${OUT_DIR}/${BASENAME}.D.prepend.out
+done
+
+echo -ne "1 apple\n2 apple\n3 Apple\n4 banana\n5 banana x\n\t6  banana\nb 7 cherry\nc 7 cherry\n" > $ROOT/fields.txt
+
+FILE=$ROOT/fields.txt
+uniq -f 1                 $FILE > ${OUT_DIR}/fields.txt.f1.out
+uniq -f 1 -c              $FILE > ${OUT_DIR}/fields.txt.f1.c.out
+uniq -f 1 -i              $FILE > ${OUT_DIR}/fields.txt.f1.i.out
+uniq -f 1 -i -c           $FILE > ${OUT_DIR}/fields.txt.f1.i.c.out
+uniq -f 1 -w 1            $FILE > ${OUT_DIR}/fields.txt.f1.w1.out
+uniq -f 2                 $FILE > ${OUT_DIR}/fields.txt.f2.out
+uniq -s 2                 $FILE > ${OUT_DIR}/fields.txt.s2.out
+uniq -s 2 -i              $FILE > ${OUT_DIR}/fields.txt.s2.i.out
+uniq -w 1                 $FILE > ${OUT_DIR}/fields.txt.w1.out
+uniq -f 1 -s 1 -w 2 -D    $FILE > ${OUT_DIR}/fields.txt.f1.s1.w2.D.out
+
+FILE=$ROOT/skip.txt
+uniq -s 1 -c              $FILE > ${OUT_DIR}/skip.txt.s1.c.out
+uniq -w 0 -c              $FILE > ${OUT_DIR}/skip.txt.w0.c.out
+uniq -f 1                 $FILE > ${OUT_DIR}/skip.txt.f1.out
+
+# uniq has no --global, so build these with awk
+FILE=$ROOT/three.txt
+awk '!seen[$0]++' $FILE > ${OUT_DIR}/three.txt.global.out
+awk '{ if (!($0 in n)) order[i++] = $0; n[$0]++ }
+     END { for (j = 0; j < i; j++) printf "%4d %s\n", n[order[j]], order[j] }' \
+    $FILE > ${OUT_DIR}/three.txt.global.c.out
+
+FILE=$ROOT/t7.txt
+awk '{ if (!($0 in n)) order[i++] = $0; n[$0]++ }
+     END { for (j = 0; j < i; j++) if (n[order[j]] > 1) print order[j] }' \
+    $FILE > ${OUT_DIR}/t7.txt.global.d.out
+awk '{ if (!($0 in n)) order[i++] = $0; n[$0]++ }
+     END { for (j = 0; j < i; j++) if (n[order[j]] == 1) printf "%4d %s\n", 1, order[j] }' \
+    $FILE > ${OUT_DIR}/t7.txt.global.u.c.out
+
+# --top K is --global -c sorted by count, keeping first occurrence order for ties
+sort -s -k1,1nr ${OUT_DIR}/three.txt.global.c.out | head -3 > ${OUT_DIR}/three.txt.top3.out
+
+echo -ne "a\0a\0b\nc\0b\nc\0d\0d" > $ROOT/zero.txt
+
+FILE=$ROOT/zero.txt
+uniq -z                          $FILE > ${OUT_DIR}/zero.txt.z.out
+uniq -z -c                       $FILE > ${OUT_DIR}/zero.txt.z.c.out
+uniq -z --all-repeated=separate  $FILE > ${OUT_DIR}/zero.txt.z.D.separate.out
+
+echo -ne "a\r\na\nb \nb\nc\r\nc\r\nc\n" > $ROOT/crlf.txt
+echo -ne "caf\xe9\ncaf\xe9\nCAF\xc9\nna\xefve\nna\xefve \n" > $ROOT/latin1.txt
+
+# uniq has no --ignore-trailing-space, so these were written by hand:
+# crlf.txt.t.c.out, latin1.txt.i.t.c.out
+for FILE in $ROOT/crlf.txt $ROOT/latin1.txt; do
+    BASENAME=$(basename "$FILE")
+    LC_ALL=C uniq      $FILE > ${OUT_DIR}/${BASENAME}.out
+    LC_ALL=C uniq -c   $FILE > ${OUT_DIR}/${BASENAME}.c.out
+done
+
+# --count-width 7 matches the width of GNU uniq -c
+uniq -c $ROOT/three.txt > ${OUT_DIR}/three.txt.c7.out
@@ -1,19 +1,380 @@
-use anyhow::{anyhow, Result};
-use clap::Parser;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, ValueEnum};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, BufWriter, Seek, Write};
+use std::mem;
+use std::os::fd::AsFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::str;
+use tempfile::NamedTempFile;
+use xxhash_rust::xxh3::xxh3_128;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
 struct Args {
-    #[arg(default_value = "-", value_name = "INPUT_FILE")]
-    input_file: String,
+    // -oを指定しない場合は[INPUT_FILE [OUTPUT_FILE]]
+    #[arg(value_name = "FILES")]
+    files: Vec<String>,
 
-    #[arg(value_name = "OUTPUT_FILE")]
-    output_file: Option<String>,
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<String>,
 
     #[arg(short, long)]
+    zero_terminated: bool,
+
+    #[arg(short, long)]
+    count: bool,
+
+    #[arg(short('d'), long)]
+    repeated: bool,
+
+    #[arg(short('u'), long)]
+    unique: bool,
+
+    #[arg(
+        short('D'),
+        long,
+        value_enum,
+        value_name = "METHOD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "none",
+        conflicts_with_all = ["count", "unique", "global"]
+    )]
+    all_repeated: Option<Delimit>,
+
+    #[arg(long)]
+    global: bool,
+
+    #[arg(long, conflicts_with_all = ["count", "repeated", "unique", "all_repeated"])]
+    approximate: bool,
+
+    #[arg(
+        long,
+        value_name = "K",
+        conflicts_with_all = ["repeated", "unique", "all_repeated", "approximate"]
+    )]
+    top: Option<usize>,
+
+    #[arg(short('f'), long, value_name = "N", default_value = "0")]
+    skip_fields: usize,
+
+    #[arg(short('s'), long, value_name = "N", default_value = "0")]
+    skip_chars: usize,
+
+    #[arg(short('w'), long, value_name = "N")]
+    check_chars: Option<usize>,
+
+    #[arg(short('i'), long)]
+    ignore_case: bool,
+
+    #[arg(long)]
+    ignore_trailing_space: bool,
+
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    #[arg(long, value_name = "N", default_value = "4")]
+    count_width: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    // 行をそのまま(-cなら行数を付けて)出力する
+    Text,
+    // 行数とタブ文字に続けて行を出力する
+    Tsv,
+    // 行数、行、最初と最後の行番号を1行1つのJSONオブジェクトで出力する
+    Json,
+}
+
+// --all-repeatedでグループの間をどう区切るか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Delimit {
+    // 区切らない
+    None,
+    // 各グループの前に空行を出力する
+    Prepend,
+    // グループの間に空行を出力する
+    Separate,
+}
+
+// 行のどの部分をどう比較するか
+#[derive(Debug, Default)]
+struct Key {
+    skip_fields: usize,
+    skip_chars: usize,
+    check_chars: Option<usize>,
+    ignore_case: bool,
+    // 行末の空白文字(\rを含む)を無視するか
+    ignore_trailing_space: bool,
+    // 行の区切りが改行ではなくNUL文字か
+    zero_terminated: bool,
+}
+
+impl Key {
+    // 比較に使う部分を取り出す
+    // 区切り文字以外はそのまま比較するので、\r\nと\nで終わる行は異なる行になる
+    // 先頭のskip_fields個のフィールド(空白の並びとそれに続く空白以外の並び)を飛ばし、
+    // さらにskip_chars文字を飛ばしてから、最大check_chars文字を比較する
+    fn extract<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let delimiter = if self.zero_terminated { b'\0' } else { b'\n' };
+        let mut rest = line.strip_suffix(&[delimiter]).unwrap_or(line);
+        if self.ignore_trailing_space {
+            rest = rest.trim_ascii_end();
+        }
+        for _ in 0..self.skip_fields {
+            let start = rest
+                .iter()
+                .position(|&b| !is_blank(b))
+                .unwrap_or(rest.len());
+            let end = rest[start..]
+                .iter()
+                .position(|&b| is_blank(b))
+                .map_or(rest.len(), |i| start + i);
+            rest = &rest[end..];
+        }
+        rest = skip_chars(rest, self.skip_chars);
+        if let Some(check_chars) = self.check_chars {
+            let end = rest.len() - skip_chars(rest, check_chars).len();
+            rest = &rest[..end];
+        }
+        if !self.ignore_case {
+            return Cow::Borrowed(rest);
+        }
+        // UTF-8として正しくなければASCIIの範囲だけ小文字にする
+        match str::from_utf8(rest) {
+            Ok(text) => Cow::Owned(text.to_lowercase().into_bytes()),
+            Err(_) => Cow::Owned(rest.to_ascii_lowercase()),
+        }
+    }
+}
+
+fn is_blank(b: u8) -> bool {
+    b == b' ' || b == b'\t'
+}
+
+// 先頭からn文字を飛ばした残り
+// UTF-8として正しくないバイトは1バイトを1文字として数える
+fn skip_chars(bytes: &[u8], n: usize) -> &[u8] {
+    let mut rest = bytes;
+    for _ in 0..n {
+        if rest.is_empty() {
+            break;
+        }
+        rest = &rest[char_len(rest)..];
+    }
+    rest
+}
+
+// 先頭の1文字のバイト数
+fn char_len(bytes: &[u8]) -> usize {
+    let len = match bytes[0] {
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => 1,
+    };
+    match bytes.get(..len) {
+        Some(c) if str::from_utf8(c).is_ok() => len,
+        _ => 1,
+    }
+}
+
+// 等しいと判定された行のまとまり
+#[derive(Debug)]
+struct Group {
+    // 元の行(改行を含む)。Emitter::keep_allがfalseなら最初の行だけを持つ
+    // locationがあれば、出力するまで空のままにしておく
+    lines: Vec<Vec<u8>>,
+    count: u64,
+    // 最初と最後の行の行番号(1から始まり、複数の入力ファイルでは通し番号)
+    first_line: u64,
+    last_line: u64,
+    // 最初の行を読み直せる位置
+    location: Option<Location>,
+}
+
+impl Group {
+    fn new(line: Vec<u8>, line_number: u64) -> Self {
+        Group {
+            lines: vec![line],
+            count: 1,
+            first_line: line_number,
+            last_line: line_number,
+            location: None,
+        }
+    }
+
+    // 最初の行の代わりに位置を覚えておく
+    fn at(location: Location, line_number: u64) -> Self {
+        Group {
+            lines: vec![],
+            location: Some(location),
+            ..Group::new(vec![], line_number)
+        }
+    }
+
+    fn add(&mut self, line: &[u8], line_number: u64, keep_all: bool) {
+        self.count += 1;
+        self.last_line = line_number;
+        if keep_all {
+            self.lines.push(line.to_vec());
+        }
+    }
+}
+
+// 長い行でも使うメモリが増えないように、キーの代わりに128ビットのダイジェストを覚えておく
+fn digest(key: &[u8]) -> u128 {
+    xxh3_128(key)
+}
+
+// 出現済みのキーの集合
+trait Seen {
+    // 初めて出現したキーならtrueを返す
+    fn insert(&mut self, digest: u128) -> bool;
+}
+
+impl Seen for HashSet<u128> {
+    fn insert(&mut self, digest: u128) -> bool {
+        HashSet::insert(self, digest)
+    }
+}
+
+// ブルームフィルタのビット数(16MiB)
+// 約1400万種類の行までなら誤判定の確率は1%程度に収まる
+const BLOOM_BITS: u64 = 1 << 27;
+
+// ブルームフィルタでビットを立てる位置の数
+const BLOOM_HASHES: u64 = 7;
+
+// 使うメモリは一定だが、初めて出現したキーを出現済みと誤判定することがある
+struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    fn new() -> Self {
+        BloomFilter {
+            bits: vec![0; (BLOOM_BITS / 64) as usize],
+        }
+    }
+}
+
+impl Seen for BloomFilter {
+    fn insert(&mut self, digest: u128) -> bool {
+        // ダイジェストの上位と下位の64ビットからBLOOM_HASHES個の位置を作る
+        let (h1, h2) = ((digest >> 64) as u64, digest as u64);
+        let mut inserted = false;
+        for i in 0..BLOOM_HASHES {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % BLOOM_BITS;
+            let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+            if self.bits[word] & mask == 0 {
+                self.bits[word] |= mask;
+                inserted = true;
+            }
+        }
+        inserted
+    }
+}
+
+// グループごとの出力方法
+trait Emitter {
+    fn emit(&mut self, group: &Group, writer: &mut dyn Write) -> Result<()>;
+
+    // グループの2行目以降も必要か
+    fn keep_all(&self) -> bool {
+        false
+    }
+}
+
+// 各グループの最初の行を出力する
+// repeatedなら繰り返された行だけ、uniqueなら繰り返されなかった行だけにする
+struct Collapse {
     count: bool,
+    repeated: bool,
+    unique: bool,
+    format: Format,
+    count_width: usize,
+    delimiter: u8,
+}
+
+impl Emitter for Collapse {
+    fn emit(&mut self, group: &Group, writer: &mut dyn Write) -> Result<()> {
+        let wanted = if group.count > 1 {
+            !self.unique
+        } else {
+            !self.repeated
+        };
+        if !wanted {
+            return Ok(());
+        }
+        let line = &group.lines[0];
+        match self.format {
+            Format::Text => {
+                if self.count {
+                    write!(writer, "{:>1$} ", group.count, self.count_width)?;
+                }
+                writer.write_all(line)?;
+            }
+            // 最後の行が区切り文字で終わっていなくても区切る
+            Format::Tsv => {
+                write!(writer, "{}\t", group.count)?;
+                writer.write_all(line.strip_suffix(&[self.delimiter]).unwrap_or(line))?;
+                writer.write_all(&[self.delimiter])?;
+            }
+            Format::Json => {
+                let line = line.strip_suffix(&[self.delimiter]).unwrap_or(line);
+                writeln!(
+                    writer,
+                    r#"{{"count":{},"line":{},"first_line":{},"last_line":{}}}"#,
+                    group.count,
+                    serde_json::to_string(&String::from_utf8_lossy(line))?,
+                    group.first_line,
+                    group.last_line,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// 繰り返された行を全て出力する
+struct AllRepeated {
+    delimit: Delimit,
+    first: bool,
+    // グループの間に出力する行の区切り文字
+    delimiter: u8,
+}
+
+impl Emitter for AllRepeated {
+    fn emit(&mut self, group: &Group, writer: &mut dyn Write) -> Result<()> {
+        if group.count < 2 {
+            return Ok(());
+        }
+        let delimit = match self.delimit {
+            Delimit::None => false,
+            Delimit::Prepend => true,
+            Delimit::Separate => !self.first,
+        };
+        self.first = false;
+        if delimit {
+            writer.write_all(&[self.delimiter])?;
+        }
+        for line in &group.lines {
+            writer.write_all(line)?;
+        }
+        Ok(())
+    }
+
+    fn keep_all(&self) -> bool {
+        true
+    }
 }
 
 fn main() {
@@ -24,42 +385,486 @@ fn main() {
 }
 
 fn run(args: Args) -> Result<()> {
-    let mut file = open(&args.input_file).map_err(|e| anyhow!("{}: {}", args.input_file, e))?;
-    let mut line = String::new();
-    let mut previous = String::new();
-    let mut writer: Box<dyn Write> = if let Some(out_file) = args.output_file {
-        Box::new(BufWriter::new(File::create(out_file)?))
+    // -oがなければ2つ目の引数を出力先とする
+    let (mut input_files, output_file) = match args.output {
+        Some(output) => (args.files, Some(output)),
+        None if args.files.len() > 2 => bail!(
+            "extra operand '{}' (use -o/--output to read multiple input files)",
+            args.files[2]
+        ),
+        None => {
+            let mut files = args.files;
+            let output = files.get(1).cloned();
+            files.truncate(1);
+            (files, output)
+        }
+    };
+    if input_files.is_empty() {
+        input_files.push("-".to_string());
+    }
+    let delimiter = if args.zero_terminated { b'\0' } else { b'\n' };
+    // tsv, jsonでは常に行数を出力する
+    let counting = args.count || args.format != Format::Text;
+    if counting && args.approximate {
+        bail!("--approximate cannot count lines");
+    }
+    if args.format != Format::Text && args.all_repeated.is_some() {
+        bail!("--all-repeated can only be used with --format text");
+    }
+    // 出力を始める前に全ての入力ファイルを開いておく
+    let files = input_files
+        .iter()
+        .map(|filename| open(filename).map_err(|e| anyhow!("{}: {}", filename, e)))
+        .collect::<Result<_>>()?;
+    let mut input = Input::new(files, delimiter);
+
+    // 出力先が入力と同じファイルなら一時ファイルに書き、成功したら置き換える
+    let mut replace: Option<(NamedTempFile, PathBuf)> = None;
+    let mut writer: Box<dyn Write> = match &output_file {
+        Some(out_file) if is_input(out_file, &input_files) => {
+            let (temp, path) = temp_output(out_file).map_err(|e| anyhow!("{}: {}", out_file, e))?;
+            let writer = BufWriter::new(temp.reopen()?);
+            replace = Some((temp, path));
+            Box::new(writer)
+        }
+        Some(out_file) => Box::new(BufWriter::new(File::create(out_file)?)),
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+    let mut emitter: Box<dyn Emitter> = match args.all_repeated {
+        Some(delimit) => Box::new(AllRepeated {
+            delimit,
+            first: true,
+            delimiter,
+        }),
+        None => Box::new(Collapse {
+            count: args.count,
+            repeated: args.repeated,
+            unique: args.unique,
+            format: args.format,
+            count_width: args.count_width,
+            delimiter,
+        }),
+    };
+
+    let key = Key {
+        skip_fields: args.skip_fields,
+        skip_chars: args.skip_chars,
+        check_chars: args.check_chars,
+        ignore_case: args.ignore_case,
+        ignore_trailing_space: args.ignore_trailing_space,
+        zero_terminated: args.zero_terminated,
+    };
+
+    if let Some(k) = args.top {
+        let mut emitter = Collapse {
+            count: true,
+            repeated: false,
+            unique: false,
+            format: args.format,
+            count_width: args.count_width,
+            delimiter,
+        };
+        for mut group in top_groups(read_global_groups(&mut input, &key)?, k) {
+            input.load(&mut group)?;
+            emitter.emit(&group, &mut writer)?;
+        }
+    } else if args.approximate {
+        read_first(&mut input, &key, &mut BloomFilter::new(), &mut writer)?;
+    } else if args.global && !counting && !args.repeated && !args.unique {
+        read_first(&mut input, &key, &mut HashSet::new(), &mut writer)?;
+    } else if args.global {
+        for mut group in read_global_groups(&mut input, &key)? {
+            input.load(&mut group)?;
+            emitter.emit(&group, &mut writer)?;
+        }
     } else {
-        Box::new(BufWriter::new(io::stdout()))
+        read_groups(&mut input, &key, emitter.as_mut(), &mut writer)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    // 途中で失敗した場合は一時ファイルが削除され、元のファイルは残る
+    if let Some((temp, path)) = replace {
+        temp.persist(&path)
+            .map_err(|e| anyhow!("{}: {}", path.display(), e.error))?;
+    }
+    Ok(())
+}
+
+// デバイス番号とiノード番号が等しければ同じファイルとみなす
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+fn is_input(out_file: &str, input_files: &[String]) -> bool {
+    let Ok(out) = fs::metadata(out_file) else {
+        return false;
     };
+    input_files
+        .iter()
+        .filter_map(|filename| match filename.as_str() {
+            // 標準入力がリダイレクトされたファイルも比べる
+            "-" => stdin_metadata().ok(),
+            _ => fs::metadata(filename).ok(),
+        })
+        .any(|input| same_file(&input, &out))
+}
+
+fn stdin_metadata() -> io::Result<Metadata> {
+    File::from(io::stdin().as_fd().try_clone_to_owned()?).metadata()
+}
 
-    if file.read_line(&mut previous)? != 0 {
-        let mut conti_count = 1u64;
-        loop {
-            let num_bytes = file.read_line(&mut line)?;
-            if previous.trim_end() == line.trim_end() {
-                conti_count += 1;
-            } else {
-                if args.count {
-                    writer.write_fmt(format_args!("{:>4} {}", conti_count, previous))?;
-                    conti_count = 1;
-                } else {
-                    writer.write_fmt(format_args!("{}", previous))?;
+// 置き換え先と同じディレクトリに一時ファイルを作り、パーミッションを引き継ぐ
+fn temp_output(out_file: &str) -> io::Result<(NamedTempFile, PathBuf)> {
+    // シンボリックリンクはリンク先を置き換える
+    let path = fs::canonicalize(out_file)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let temp = NamedTempFile::new_in(dir)?;
+    fs::set_permissions(temp.path(), fs::metadata(&path)?.permissions())?;
+    Ok((temp, path))
+}
+
+// キーが等しい連続する行をグループにまとめてemitterに渡す
+fn read_groups(
+    input: &mut Input,
+    key: &Key,
+    emitter: &mut dyn Emitter,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    let keep_all = emitter.keep_all();
+    // 現在のグループと、その最初の行から取り出したキー
+    let mut group: Option<(Group, Vec<u8>)> = None;
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line_key = key.extract(&line);
+        match &mut group {
+            Some((group, group_key)) if *group_key == *line_key => {
+                group.add(&line, input.line_number, keep_all);
+            }
+            _ => {
+                if let Some((group, _)) = group.take() {
+                    emitter.emit(&group, writer)?;
                 }
-                previous = line.clone();
+                let new_group = Group::new(line.clone(), input.line_number);
+                group = Some((new_group, line_key.into_owned()));
             }
-            if num_bytes == 0 {
-                break;
+        }
+    }
+    if let Some((group, _)) = group {
+        emitter.emit(&group, writer)?;
+    }
+    Ok(())
+}
+
+// 隣接していない行も含めて、キーが等しい行を最初に出現した順にグループにまとめる
+// 数え終えるまで出力できないので、グループごとに最初の行の位置を覚えておく
+// 読み直せない標準入力やパイプでは、長い行でも最初の行そのものを覚えておくことになる
+fn read_global_groups(input: &mut Input, key: &Key) -> Result<Vec<Group>> {
+    let mut groups: Vec<Group> = vec![];
+    let mut indices: HashMap<u128, usize> = HashMap::new();
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        match indices.entry(digest(&key.extract(&line))) {
+            Entry::Occupied(entry) => groups[*entry.get()].add(&line, input.line_number, false),
+            Entry::Vacant(entry) => {
+                entry.insert(groups.len());
+                groups.push(match input.location() {
+                    Some(location) => Group::at(location, input.line_number),
+                    None => Group::new(line.clone(), input.line_number),
+                });
             }
-            line.clear();
+        }
+    }
+    Ok(groups)
+}
+
+// 行数の多い順にk個のグループを返す
+// 行数が同じなら先に出現したグループを優先する
+fn top_groups(groups: Vec<Group>, k: usize) -> Vec<Group> {
+    // 残す候補のうち最も優先度の低いものが先頭に来るヒープ
+    let mut heap = BinaryHeap::new();
+    for (i, group) in groups.iter().enumerate() {
+        heap.push(Reverse((group.count, Reverse(i))));
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+    // into_sorted_vecは優先度の高い順に並ぶ
+    let mut groups: Vec<Option<Group>> = groups.into_iter().map(Some).collect();
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((_, Reverse(i)))| groups[i].take().unwrap())
+        .collect()
+}
+
+// 隣接していない行も含めて、キーが初めて出現した行だけを出力する
+fn read_first(
+    input: &mut Input,
+    key: &Key,
+    seen: &mut dyn Seen,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break;
+        }
+        if seen.insert(digest(&key.extract(&line))) {
+            writer.write_all(&line)?;
         }
     }
     Ok(())
 }
 
-fn open(filename: &str) -> Result<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+// 入力ファイルの種類
+enum Source {
+    // 通常のファイルは後から位置を指定して読み直せる
+    File(BufReader<File>),
+    // 標準入力やパイプは一度しか読めない
+    Stream(Box<dyn BufRead>),
+}
+
+impl Source {
+    fn reader(&mut self) -> &mut dyn BufRead {
+        match self {
+            Source::File(file) => file,
+            Source::Stream(stream) => stream,
+        }
+    }
+}
+
+// 入力ファイルの番号と、そのファイルの中で行が始まるバイト位置
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    file: usize,
+    offset: u64,
+}
+
+// 入力ファイルを順に、区切り文字までを1行として読む
+// 1つの行が2つのファイルにまたがることはない
+struct Input {
+    files: Vec<Source>,
+    current: usize,
+    delimiter: u8,
+    buf: Vec<u8>,
+    // 最後に読んだ行の行番号
+    line_number: u64,
+    // 現在のファイルで最後に読んだ行の先頭と、次に読む位置
+    line_start: u64,
+    position: u64,
+}
+
+impl Input {
+    fn new(files: Vec<Source>, delimiter: u8) -> Self {
+        Input {
+            files,
+            current: 0,
+            delimiter,
+            buf: vec![],
+            line_number: 0,
+            line_start: 0,
+            position: 0,
+        }
+    }
+
+    // 区切り文字も含めてlineに追加する。全てのファイルを読み終えたら0を返す
+    fn read_line(&mut self, line: &mut Vec<u8>) -> Result<usize> {
+        while let Some(file) = self.files.get_mut(self.current) {
+            self.buf.clear();
+            let bytes = file.reader().read_until(self.delimiter, &mut self.buf)?;
+            if bytes == 0 {
+                self.current += 1;
+                self.position = 0;
+                continue;
+            }
+            self.line_start = self.position;
+            self.position += bytes as u64;
+            self.terminate(self.current);
+            line.extend_from_slice(&self.buf);
+            self.line_number += 1;
+            return Ok(self.buf.len());
+        }
+        Ok(0)
+    }
+
+    // 区切り文字で終わっていないファイルの最後の行も、後に続くファイルがあれば区切る
+    fn terminate(&mut self, file: usize) {
+        if self.buf.last() != Some(&self.delimiter) && file + 1 < self.files.len() {
+            self.buf.push(self.delimiter);
+        }
+    }
+
+    // 最後に読んだ行を読み直せるならその位置を返す
+    fn location(&self) -> Option<Location> {
+        match self.files.get(self.current) {
+            Some(Source::File(_)) => Some(Location {
+                file: self.current,
+                offset: self.line_start,
+            }),
+            _ => None,
+        }
+    }
+
+    // グループの最初の行を位置しか覚えていなければ、ファイルから読み直す
+    fn load(&mut self, group: &mut Group) -> Result<()> {
+        let Some(location) = group.location.take() else {
+            return Ok(());
+        };
+        let Source::File(file) = &mut self.files[location.file] else {
+            unreachable!("only regular files have a location");
+        };
+        // 先に出現した行から順に読み直すことが多いので、読み込み済みのバッファを活かす
+        let position = file.stream_position()?;
+        file.seek_relative(location.offset as i64 - position as i64)?;
+        self.buf.clear();
+        file.read_until(self.delimiter, &mut self.buf)?;
+        self.terminate(location.file);
+        group.lines.push(mem::take(&mut self.buf));
+        Ok(())
+    }
+}
+
+fn open(filename: &str) -> Result<Source> {
+    if filename == "-" {
+        return Ok(Source::Stream(Box::new(BufReader::new(io::stdin()))));
+    }
+    let file = File::open(filename)?;
+    if file.metadata()?.is_file() {
+        Ok(Source::File(BufReader::new(file)))
+    } else {
+        Ok(Source::Stream(Box::new(BufReader::new(file))))
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::{digest, top_groups, BloomFilter, Group, Key, Seen};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_seen() {
+        let mut exact = HashSet::new();
+        let mut bloom = BloomFilter::new();
+        for seen in [&mut exact as &mut dyn Seen, &mut bloom] {
+            assert!(seen.insert(digest(b"a")));
+            assert!(seen.insert(digest(b"b")));
+            assert!(!seen.insert(digest(b"a")));
+            assert!(!seen.insert(digest(b"b")));
+        }
+
+        // 要素が少なければブルームフィルタもほぼ誤判定しない
+        let mut bloom = BloomFilter::new();
+        let inserted = (0..10000)
+            .filter(|i| bloom.insert(digest(i.to_string().as_bytes())))
+            .count();
+        assert!(inserted >= 9990);
+    }
+
+    #[test]
+    fn test_top_groups() {
+        let groups = |counts: &[u64]| -> Vec<Group> {
+            counts
+                .iter()
+                .enumerate()
+                .map(|(i, &count)| Group {
+                    count,
+                    ..Group::new(i.to_string().into_bytes(), i as u64 + 1)
+                })
+                .collect()
+        };
+        let top = |counts: &[u64], k: usize| -> Vec<Vec<u8>> {
+            top_groups(groups(counts), k)
+                .into_iter()
+                .map(|group| group.lines[0].clone())
+                .collect()
+        };
+        assert_eq!(top(&[1, 3, 2, 3, 1], 3), [b"1", b"3", b"2"]);
+        // 行数が同じなら先に出現した順
+        assert_eq!(top(&[2, 1, 2, 2], 2), [b"0", b"2"]);
+        assert_eq!(top(&[1, 1], 5), [b"0", b"1"]);
+        assert!(top(&[1, 2], 0).is_empty());
+        assert!(top(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn test_extract() {
+        let key = Key::default();
+        assert_eq!(*key.extract(b"a b\n"), b"a b"[..]);
+
+        let key = Key {
+            skip_fields: 1,
+            ..Default::default()
+        };
+        assert_eq!(*key.extract(b"  one  two three\n"), b"  two three"[..]);
+        assert_eq!(*key.extract(b"one"), b""[..]);
+
+        let key = Key {
+            skip_fields: 1,
+            skip_chars: 2,
+            check_chars: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(*key.extract(b"one\t two three\n"), b"two"[..]);
+        assert_eq!(
+            *key.extract("one あいうえお\n".as_bytes()),
+            *"いうえ".as_bytes()
+        );
+
+        let key = Key {
+            check_chars: Some(0),
+            ignore_case: true,
+            ..Default::default()
+        };
+        assert_eq!(*key.extract(b"ABC\n"), b""[..]);
+
+        let key = Key {
+            ignore_case: true,
+            ..Default::default()
+        };
+        assert_eq!(*key.extract(b"AbC\n"), b"abc"[..]);
+        assert_eq!(*key.extract("ÀÉ\n".as_bytes()), *"àé".as_bytes());
+        // UTF-8として正しくなければASCIIの範囲だけ小文字にする
+        assert_eq!(*key.extract(b"CAF\xc9\n"), b"caf\xc9"[..]);
+    }
+
+    #[test]
+    fn test_extract_bytes() {
+        // 既定では区切り文字以外をそのまま比較する
+        let key = Key::default();
+        assert_eq!(*key.extract(b"a \r\n"), b"a \r"[..]);
+        assert_eq!(*key.extract(b"a"), b"a"[..]);
+
+        let key = Key {
+            ignore_trailing_space: true,
+            ..Default::default()
+        };
+        assert_eq!(*key.extract(b"a \r\n"), b"a"[..]);
+        assert_eq!(*key.extract(b"a\t\n"), b"a"[..]);
+
+        let key = Key {
+            zero_terminated: true,
+            ..Default::default()
+        };
+        assert_eq!(*key.extract(b"a\n\0"), b"a\n"[..]);
+
+        // UTF-8として正しくないバイトは1文字として数える
+        let key = Key {
+            skip_chars: 1,
+            check_chars: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            *key.extract(b"\xe9\xe9\xe3\x81\x82x\n"),
+            b"\xe9\xe3\x81\x82"[..]
+        );
+        assert_eq!(*key.extract(b"\xe3\x81x\n"), b"\x81x"[..]);
     }
 }
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use assert_cmd::prelude::*;
 use assert_cmd::Command;
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
@@ -80,6 +81,52 @@ const T6: Test = Test {
     out_count: "tests/expected/t6.txt.c.out",
 };
 
+const T7: Test = Test {
+    input: "tests/inputs/t7.txt",
+    out: "tests/expected/t7.txt.out",
+    out_count: "tests/expected/t7.txt.c.out",
+};
+
+const FIELDS: Test = Test {
+    input: "tests/inputs/fields.txt",
+    out: "tests/expected/fields.txt.out",
+    out_count: "tests/expected/fields.txt.c.out",
+};
+
+const ZERO: Test = Test {
+    input: "tests/inputs/zero.txt",
+    out: "tests/expected/zero.txt.out",
+    out_count: "tests/expected/zero.txt.c.out",
+};
+
+const CRLF: Test = Test {
+    input: "tests/inputs/crlf.txt",
+    out: "tests/expected/crlf.txt.out",
+    out_count: "tests/expected/crlf.txt.c.out",
+};
+
+const LATIN1: Test = Test {
+    input: "tests/inputs/latin1.txt",
+    out: "tests/expected/latin1.txt.out",
+    out_count: "tests/expected/latin1.txt.c.out",
+};
+
+// -d, -u, -Dの各モードの引数と、期待する出力のファイル名に付ける接尾辞
+const MODES: &[(&[&str], &str)] = &[
+    (&["-d"], "d"),
+    (&["-d", "-c"], "d.c"),
+    (&["--repeated", "--count"], "d.c"),
+    (&["-u"], "u"),
+    (&["-u", "-c"], "u.c"),
+    (&["--unique"], "u"),
+    (&["-D"], "D"),
+    (&["--all-repeated"], "D"),
+    (&["--all-repeated=none"], "D"),
+    (&["-d", "-D"], "D"),
+    (&["--all-repeated=separate"], "D.separate"),
+    (&["--all-repeated=prepend"], "D.prepend"),
+];
+
 // --------------------------------------------------
 fn gen_bad_file() -> String {
     loop {
@@ -108,6 +155,32 @@ fn dies_bad_file() -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_all_repeated_and_count() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-D", "-c", T7.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--all-repeated[=<METHOD>]' cannot be used with '--count'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_all_repeated_method() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--all-repeated=both", T7.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'both' for '--all-repeated[=<METHOD>]'",
+        ));
+    Ok(())
+}
+
 // --------------------------------------------------
 // HELPER FUNCTIONS
 fn run(test: &Test) -> Result<()> {
@@ -222,6 +295,53 @@ fn run_stdin_outfile_count(test: &Test) -> Result<()> {
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_modes(test: &Test) -> Result<()> {
+    let input = fs::read_to_string(test.input)?;
+    for (args, suffix) in MODES {
+        let expected_file = test.out.replace(".out", &format!(".{suffix}.out"));
+        let expected = fs::read_to_string(expected_file)?;
+        let output = Command::cargo_bin(PRG)?
+            .args(*args)
+            .arg(test.input)
+            .output()
+            .expect("fail");
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+        assert_eq!(stdout, expected, "{args:?}");
+
+        let output = Command::cargo_bin(PRG)?
+            .args(*args)
+            .write_stdin(input.clone())
+            .output()
+            .expect("fail");
+        assert!(output.status.success());
+
+        let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+        assert_eq!(stdout, expected, "{args:?}");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// UTF-8として正しくない出力もあるのでバイト列で比較する
+fn run_key(test: &Test, args: &[&str], suffix: &str) -> Result<()> {
+    let expected_file = match suffix {
+        "" => test.out.to_string(),
+        _ => test.out.replace(".out", &format!(".{suffix}.out")),
+    };
+    let expected = fs::read(expected_file)?;
+    let output = Command::cargo_bin(PRG)?
+        .args(args)
+        .arg(test.input)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> Result<()> {
@@ -330,6 +450,11 @@ fn two_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&TWO)
 }
 
+#[test]
+fn two_modes() -> Result<()> {
+    run_modes(&TWO)
+}
+
 // --------------------------------------------------
 #[test]
 fn three() -> Result<()> {
@@ -366,6 +491,11 @@ fn three_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&THREE)
 }
 
+#[test]
+fn three_modes() -> Result<()> {
+    run_modes(&THREE)
+}
+
 // --------------------------------------------------
 #[test]
 fn skip() -> Result<()> {
@@ -438,6 +568,11 @@ fn t1_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&T1)
 }
 
+#[test]
+fn t1_modes() -> Result<()> {
+    run_modes(&T1)
+}
+
 // --------------------------------------------------
 #[test]
 fn t2() -> Result<()> {
@@ -474,6 +609,11 @@ fn t2_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&T2)
 }
 
+#[test]
+fn t2_modes() -> Result<()> {
+    run_modes(&T2)
+}
+
 // --------------------------------------------------
 #[test]
 fn t3() -> Result<()> {
@@ -510,6 +650,11 @@ fn t3_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&T3)
 }
 
+#[test]
+fn t3_modes() -> Result<()> {
+    run_modes(&T3)
+}
+
 // --------------------------------------------------
 #[test]
 fn t4() -> Result<()> {
@@ -546,6 +691,11 @@ fn t4_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&T4)
 }
 
+#[test]
+fn t4_modes() -> Result<()> {
+    run_modes(&T4)
+}
+
 // --------------------------------------------------
 #[test]
 fn t5() -> Result<()> {
@@ -582,6 +732,11 @@ fn t5_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&T5)
 }
 
+#[test]
+fn t5_modes() -> Result<()> {
+    run_modes(&T5)
+}
+
 // --------------------------------------------------
 #[test]
 fn t6() -> Result<()> {
@@ -617,3 +772,537 @@ fn t6_outfile_count() -> Result<()> {
 fn t6_stdin_outfile_count() -> Result<()> {
     run_stdin_outfile_count(&T6)
 }
+
+#[test]
+fn t6_modes() -> Result<()> {
+    run_modes(&T6)
+}
+
+// --------------------------------------------------
+#[test]
+fn t7() -> Result<()> {
+    run(&T7)
+}
+
+#[test]
+fn t7_count() -> Result<()> {
+    run_count(&T7)
+}
+
+#[test]
+fn t7_stdin() -> Result<()> {
+    run_stdin(&T7)
+}
+
+#[test]
+fn t7_stdin_count() -> Result<()> {
+    run_stdin_count(&T7)
+}
+
+#[test]
+fn t7_outfile() -> Result<()> {
+    run_outfile(&T7)
+}
+
+#[test]
+fn t7_outfile_count() -> Result<()> {
+    run_outfile_count(&T7)
+}
+
+#[test]
+fn t7_stdin_outfile_count() -> Result<()> {
+    run_stdin_outfile_count(&T7)
+}
+
+#[test]
+fn t7_modes() -> Result<()> {
+    run_modes(&T7)
+}
+
+// --------------------------------------------------
+#[test]
+fn fields() -> Result<()> {
+    run(&FIELDS)
+}
+
+#[test]
+fn fields_count() -> Result<()> {
+    run_count(&FIELDS)
+}
+
+#[test]
+fn fields_skip_fields() -> Result<()> {
+    run_key(&FIELDS, &["-f", "1"], "f1")?;
+    run_key(&FIELDS, &["--skip-fields", "2"], "f2")
+}
+
+#[test]
+fn fields_skip_fields_count() -> Result<()> {
+    // 数えた行の代わりにグループの最初の行を出力する
+    run_key(&FIELDS, &["-f", "1", "-c"], "f1.c")
+}
+
+#[test]
+fn fields_ignore_case() -> Result<()> {
+    run_key(&FIELDS, &["-f", "1", "-i"], "f1.i")?;
+    run_key(&FIELDS, &["-f1", "--ignore-case", "-c"], "f1.i.c")
+}
+
+#[test]
+fn fields_skip_chars() -> Result<()> {
+    run_key(&FIELDS, &["-s", "2"], "s2")?;
+    run_key(&FIELDS, &["--skip-chars", "2", "-i"], "s2.i")
+}
+
+#[test]
+fn fields_check_chars() -> Result<()> {
+    run_key(&FIELDS, &["-w", "1"], "w1")?;
+    run_key(&FIELDS, &["-f", "1", "--check-chars", "1"], "f1.w1")
+}
+
+#[test]
+fn fields_all_repeated() -> Result<()> {
+    run_key(
+        &FIELDS,
+        &["-f", "1", "-s", "1", "-w", "2", "-D"],
+        "f1.s1.w2.D",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn skip_skip_chars() -> Result<()> {
+    run_key(&SKIP, &["-s", "1", "-c"], "s1.c")
+}
+
+#[test]
+fn skip_check_chars() -> Result<()> {
+    run_key(&SKIP, &["-w", "0", "-c"], "w0.c")
+}
+
+#[test]
+fn skip_skip_fields() -> Result<()> {
+    run_key(&SKIP, &["-f", "1"], "f1")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_skip_fields() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "x", FIELDS.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'x' for '--skip-fields <N>'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn three_global() -> Result<()> {
+    run_key(&THREE, &["--global"], "global")?;
+    run_key(&THREE, &["--global", "-c"], "global.c")
+}
+
+#[test]
+fn three_global_stdin() -> Result<()> {
+    // 読み直せない標準入力でも同じ結果になる
+    let expected = fs::read(THREE.out.replace(".out", ".global.c.out"))?;
+    let output = Command::cargo_bin(PRG)?
+        .args(["--global", "-c"])
+        .pipe_stdin(THREE.input)?
+        .output()?;
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+    Ok(())
+}
+
+#[test]
+fn global_rereads_multiple_files() -> Result<()> {
+    // 最初の行はファイルから読み直すので、最後の行が区切り文字で終わっていなくても区切られる
+    let dir = tempfile::tempdir()?;
+    let first = dir.path().join("first.txt");
+    let second = dir.path().join("second.txt");
+    let out = dir.path().join("out.txt");
+    fs::write(&first, "b\na\nb")?;
+    fs::write(&second, "c\na\nb\n")?;
+
+    Command::cargo_bin(PRG)?
+        .args(["--global", "-c", "-o"])
+        .args([&out, &first, &second])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&out)?, "   3 b\n   2 a\n   1 c\n");
+
+    Command::cargo_bin(PRG)?
+        .args(["--top", "1", "-o"])
+        .args([&out, &second, &first])
+        .assert()
+        .success();
+    assert_eq!(fs::read_to_string(&out)?, "   3 b\n");
+    Ok(())
+}
+
+#[test]
+fn three_approximate() -> Result<()> {
+    run_key(&THREE, &["--approximate"], "global")
+}
+
+#[test]
+fn t7_global_repeated() -> Result<()> {
+    run_key(&T7, &["--global", "-d"], "global.d")
+}
+
+#[test]
+fn t7_global_unique() -> Result<()> {
+    run_key(&T7, &["--global", "-u", "-c"], "global.u.c")
+}
+
+#[test]
+fn fields_global() -> Result<()> {
+    // 数えた行の代わりに最初に出現した行を出力する
+    run_key(
+        &FIELDS,
+        &["--global", "-c", "-f", "1", "-i"],
+        "global.f1.i.c",
+    )
+}
+
+#[test]
+fn dies_approximate_and_count() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--approximate", "-c", THREE.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--approximate' cannot be used with '--count'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn three_top() -> Result<()> {
+    run_key(&THREE, &["--top", "3"], "top3")
+}
+
+#[test]
+fn t7_top() -> Result<()> {
+    run_key(&T7, &["--top", "2"], "top2")
+}
+
+#[test]
+fn fields_top() -> Result<()> {
+    run_key(&FIELDS, &["--top", "2", "-f", "1", "-i"], "top2.f1.i")
+}
+
+#[test]
+fn dies_top_and_unique() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--top", "1", "-u", THREE.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--top <K>' cannot be used with '--unique'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_terminated() -> Result<()> {
+    run_key(&ZERO, &["-z"], "z")?;
+    run_key(&ZERO, &["--zero-terminated", "-c"], "z.c")?;
+    run_key(&ZERO, &["-z", "--all-repeated=separate"], "z.D.separate")
+}
+
+// --------------------------------------------------
+fn run_multi(args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read_to_string(expected_file)?;
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_str().unwrap();
+
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .args(["-o", outpath])
+        .args([T3.input, T1.input, T5.input, T2.input])
+        .assert()
+        .success()
+        .stdout("");
+    let contents = fs::read_to_string(outpath)?;
+    assert_eq!(&expected, &contents);
+
+    Ok(())
+}
+
+#[test]
+fn multiple_inputs() -> Result<()> {
+    // 改行で終わっていないファイルの最後の行も次のファイルの行とはつながらない
+    run_multi(&[], "tests/expected/multi.out")
+}
+
+#[test]
+fn multiple_inputs_count() -> Result<()> {
+    run_multi(&["--count"], "tests/expected/multi.c.out")
+}
+
+#[test]
+fn multiple_inputs_stdin() -> Result<()> {
+    let input = fs::read_to_string(T1.input)?;
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_str().unwrap();
+
+    Command::cargo_bin(PRG)?
+        .args(["-c", "--output", outpath, T5.input, "-"])
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("");
+    let contents = fs::read_to_string(outpath)?;
+    assert_eq!(contents, "   1 b\n   4 a\n");
+
+    Ok(())
+}
+
+#[test]
+fn dies_multiple_inputs_without_output() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([T1.input, T2.input, T3.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "extra operand '{}'",
+            T3.input
+        )));
+    Ok(())
+}
+
+#[test]
+fn dies_multiple_inputs_bad_file() -> Result<()> {
+    let bad = gen_bad_file();
+    let expected = format!("{bad}: .* [(]os error 2[)]");
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_str().unwrap();
+
+    Command::cargo_bin(PRG)?
+        .args(["-o", outpath, T1.input, &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn crlf() -> Result<()> {
+    // \r\nと\nで終わる行や、末尾の空白だけが異なる行は別の行として扱う
+    run(&CRLF)
+}
+
+#[test]
+fn crlf_count() -> Result<()> {
+    run_count(&CRLF)
+}
+
+#[test]
+fn crlf_ignore_trailing_space() -> Result<()> {
+    // 最初の行の行末がそのまま出力される
+    run_key(&CRLF, &["--ignore-trailing-space", "-c"], "t.c")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1() -> Result<()> {
+    run_key(&LATIN1, &[], "")
+}
+
+#[test]
+fn latin1_count() -> Result<()> {
+    run_key(&LATIN1, &["-c"], "c")
+}
+
+#[test]
+fn latin1_ignore_case() -> Result<()> {
+    run_key(&LATIN1, &["-i", "-c", "--ignore-trailing-space"], "i.t.c")
+}
+
+#[test]
+fn latin1_stdin() -> Result<()> {
+    let input = fs::read(LATIN1.input)?;
+    let expected = fs::read(LATIN1.out)?;
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn three_count_width() -> Result<()> {
+    run_key(&THREE, &["-c", "--count-width", "7"], "c7")
+}
+
+#[test]
+fn three_tsv() -> Result<()> {
+    run_key(&THREE, &["--format", "tsv"], "tsv")
+}
+
+#[test]
+fn t2_tsv() -> Result<()> {
+    // 区切り文字で終わっていない最後の行も区切る
+    run_key(&T2, &["--format", "tsv"], "tsv")
+}
+
+#[test]
+fn three_json() -> Result<()> {
+    run_key(&THREE, &["--format", "json"], "json")?;
+    run_key(&THREE, &["--format", "json", "--global"], "global.json")?;
+    run_key(&THREE, &["--format", "json", "--top", "2"], "top2.json")
+}
+
+#[test]
+fn fields_json() -> Result<()> {
+    run_key(&FIELDS, &["--format", "json", "-f", "1", "-i"], "f1.i.json")
+}
+
+#[test]
+fn latin1_json() -> Result<()> {
+    run_key(&LATIN1, &["--format", "json"], "json")
+}
+
+#[test]
+fn dies_json_all_repeated() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--format", "json", "-D", T1.input])
+        .assert()
+        .failure()
+        .stderr("--all-repeated can only be used with --format text\n");
+    Ok(())
+}
+
+#[test]
+fn dies_tsv_approximate() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--format", "tsv", "--approximate", T1.input])
+        .assert()
+        .failure()
+        .stderr("--approximate cannot count lines\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_inplace(test: &Test, args: &[&str], expected_file: &str) -> Result<()> {
+    let expected = fs::read_to_string(expected_file)?;
+    let file = NamedTempFile::new()?;
+    fs::copy(test.input, file.path())?;
+    let path = &file.path().to_str().unwrap();
+
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .args([path, path])
+        .assert()
+        .success()
+        .stdout("");
+    let contents = fs::read_to_string(path)?;
+    assert_eq!(&expected, &contents);
+
+    Ok(())
+}
+
+#[test]
+fn inplace() -> Result<()> {
+    // 入力と同じファイルに出力しても入力が失われない
+    run_inplace(&THREE, &[], THREE.out)?;
+    run_inplace(&THREE, &["-c"], THREE.out_count)?;
+    run_inplace(&T6, &[], T6.out)
+}
+
+#[test]
+fn inplace_output_option() -> Result<()> {
+    let file = NamedTempFile::new()?;
+    fs::copy(THREE.input, file.path())?;
+    let path = &file.path().to_str().unwrap();
+
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-o", path, EMPTY.input, path])
+        .assert()
+        .success()
+        .stdout("");
+    let expected = fs::read_to_string(THREE.out_count)?;
+    let contents = fs::read_to_string(path)?;
+    assert_eq!(&expected, &contents);
+
+    Ok(())
+}
+
+#[test]
+fn inplace_stdin() -> Result<()> {
+    let file = NamedTempFile::new()?;
+    fs::copy(THREE.input, file.path())?;
+    let path = &file.path().to_str().unwrap();
+
+    // 標準入力にリダイレクトしたファイルと同じファイルに出力しても入力が失われない
+    // pipe_stdinは内容をパイプで渡すので、ファイルを直接標準入力にする
+    std::process::Command::cargo_bin(PRG)?
+        .args(["-c", "-o", path, "-", EMPTY.input])
+        .stdin(fs::File::open(path)?)
+        .assert()
+        .success()
+        .stdout("");
+    let expected = fs::read_to_string(THREE.out_count)?;
+    let contents = fs::read_to_string(path)?;
+    assert_eq!(&expected, &contents);
+
+    Ok(())
+}
+
+#[test]
+fn inplace_symlink() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let target = dir.path().join("three.txt");
+    let link = dir.path().join("link.txt");
+    fs::copy(THREE.input, &target)?;
+    std::os::unix::fs::symlink(&target, &link)?;
+
+    // シンボリックリンク経由で指定してもリンク先が置き換わる
+    Command::cargo_bin(PRG)?
+        .args([target.to_str().unwrap(), link.to_str().unwrap()])
+        .assert()
+        .success();
+    let expected = fs::read_to_string(THREE.out)?;
+    assert_eq!(expected, fs::read_to_string(&target)?);
+    assert!(fs::symlink_metadata(&link)?.file_type().is_symlink());
+
+    Ok(())
+}
+
+#[test]
+fn inplace_failure_keeps_input() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("three.txt");
+    fs::copy(THREE.input, &path)?;
+    // ディレクトリは開けるが読み込みに失敗するので、一時ファイルを作った後でエラーになる
+    let unreadable = dir.path().join("dir");
+    fs::create_dir(&unreadable)?;
+    let path_str = path.to_str().unwrap();
+
+    Command::cargo_bin(PRG)?
+        .args(["-o", path_str, path_str, unreadable.to_str().unwrap()])
+        .assert()
+        .failure();
+    let expected = fs::read_to_string(THREE.input)?;
+    assert_eq!(expected, fs::read_to_string(&path)?);
+
+    // 一時ファイルが残っていない
+    let mut entries = fs::read_dir(dir.path())?
+        .map(|entry| Ok(entry?.file_name()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+    assert_eq!(entries, ["dir", "three.txt"]);
+
+    Ok(())
+}
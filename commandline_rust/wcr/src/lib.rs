@@ -0,0 +1,824 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::iter::Sum;
+use std::ops::Add;
+use std::str;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use unicode_width::UnicodeWidthChar;
+
+// 単語の区切り方
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WordsMode {
+    // 空白文字で区切る
+    #[default]
+    Whitespace,
+    // UAX #29の単語境界で区切る(空白のない日本語や中国語も単語に分かれる)
+    Unicode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CountOptions {
+    pub words_mode: WordsMode,
+    // 書記素クラスタを数えるか(数えない場合num_graphemesは0になる)
+    pub graphemes: bool,
+    // 行の長さの統計を取るか(取らない場合line_statsはNoneになる)
+    pub stats: bool,
+    // 改行で終わっていない最後の行を行数に含めるか
+    pub count_unterminated: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileInfo {
+    pub num_lines: usize,
+    // \r\nで終わる行と\nだけで終わる行の数
+    pub num_crlf: usize,
+    pub num_lf: usize,
+    pub num_words: usize,
+    pub num_bytes: usize,
+    pub num_chars: usize,
+    pub num_graphemes: usize,
+    pub num_max_line: usize,
+    pub line_stats: Option<LineStats>,
+}
+
+// 複数のファイルの合計
+// 最大行長は各ファイルの最大値、行の長さの統計は両方の分布を合わせたものになる
+impl Add for FileInfo {
+    type Output = FileInfo;
+
+    fn add(self, other: FileInfo) -> FileInfo {
+        FileInfo {
+            num_lines: self.num_lines + other.num_lines,
+            num_crlf: self.num_crlf + other.num_crlf,
+            num_lf: self.num_lf + other.num_lf,
+            num_words: self.num_words + other.num_words,
+            num_bytes: self.num_bytes + other.num_bytes,
+            num_chars: self.num_chars + other.num_chars,
+            num_graphemes: self.num_graphemes + other.num_graphemes,
+            num_max_line: self.num_max_line.max(other.num_max_line),
+            line_stats: match (self.line_stats, other.line_stats) {
+                (Some(mut stats), Some(other)) => {
+                    stats.merge(&other);
+                    Some(stats)
+                }
+                (stats, other) => stats.or(other),
+            },
+        }
+    }
+}
+
+impl Sum for FileInfo {
+    fn sum<I: Iterator<Item = FileInfo>>(iter: I) -> FileInfo {
+        iter.fold(FileInfo::default(), Add::add)
+    }
+}
+
+impl<'a> Sum<&'a FileInfo> for FileInfo {
+    fn sum<I: Iterator<Item = &'a FileInfo>>(iter: I) -> FileInfo {
+        iter.cloned().sum()
+    }
+}
+
+// 行の長さ(改行を除いた文字数)の分布
+// 長さごとの行数だけを持つので、行数が多くても使うメモリは異なる長さの種類の数で済む
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineStats {
+    lengths: BTreeMap<usize, usize>,
+}
+
+impl LineStats {
+    fn add(&mut self, len: usize) {
+        *self.lengths.entry(len).or_default() += 1;
+    }
+
+    fn merge(&mut self, other: &LineStats) {
+        for (&len, &count) in &other.lengths {
+            *self.lengths.entry(len).or_default() += count;
+        }
+    }
+
+    pub fn num_lines(&self) -> usize {
+        self.lengths.values().sum()
+    }
+
+    pub fn num_empty(&self) -> usize {
+        self.lengths.get(&0).copied().unwrap_or(0)
+    }
+
+    pub fn min(&self) -> Option<usize> {
+        self.lengths.keys().next().copied()
+    }
+
+    pub fn max(&self) -> Option<usize> {
+        self.lengths.keys().next_back().copied()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        let num_lines = self.num_lines();
+        let sum: usize = self.lengths.iter().map(|(len, n)| len * n).sum();
+        (num_lines > 0).then(|| sum as f64 / num_lines as f64)
+    }
+
+    // 行数が偶数の場合は中央の2つの平均
+    pub fn median(&self) -> Option<f64> {
+        let num_lines = self.num_lines();
+        if num_lines == 0 {
+            return None;
+        }
+        let nth = |n: usize| {
+            let mut seen = 0;
+            for (&len, &count) in &self.lengths {
+                seen += count;
+                if seen > n {
+                    return len;
+                }
+            }
+            unreachable!()
+        };
+        let upper = nth(num_lines / 2);
+        if num_lines % 2 == 1 {
+            Some(upper as f64)
+        } else {
+            Some((nth(num_lines / 2 - 1) + upper) as f64 / 2.0)
+        }
+    }
+
+    // 0, 1, 2-3, 4-7, ... のように2の冪で区切った範囲ごとの行数
+    // 最短の行から最長の行までの範囲を、行がない範囲も含めて返す
+    pub fn histogram(&self) -> Vec<(usize, usize, usize)> {
+        let bucket = |len: usize| (usize::BITS - len.leading_zeros()) as usize;
+        let (Some(min), Some(max)) = (self.min(), self.max()) else {
+            return vec![];
+        };
+        let mut counts = vec![0; bucket(max) - bucket(min) + 1];
+        for (&len, &count) in &self.lengths {
+            counts[bucket(len) - bucket(min)] += count;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| match bucket(min) + i {
+                0 => (0, 0, count),
+                b => (1 << (b - 1), (1 << b) - 1, count),
+            })
+            .collect()
+    }
+}
+
+// タブ文字は次のこの倍数の桁まで進める
+const TAB_WIDTH: usize = 8;
+
+// countで一度に読み込むバイト数
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// UTF-8の1文字の最大バイト数
+// チャンクの境界で途切れた文字はこれ未満のバイト数で次のチャンクに持ち越される
+const MAX_UTF8_LEN: usize = 4;
+
+// 単語を数えるために溜める1行の長さの目安
+// 1行がこれより長くなったら、後に続く文字で変わらない境界までの単語を数えて捨てる
+const LINE_LIMIT: usize = 64 * 1024;
+
+// チャンクをまたいで数え上げるための状態
+#[derive(Debug, Default)]
+struct Counter {
+    num_lines: usize,
+    num_crlf: usize,
+    num_words: usize,
+    num_bytes: usize,
+    num_chars: usize,
+    num_graphemes: usize,
+    num_max_line: usize,
+    in_word: bool,
+    last_byte: Option<u8>,
+    line_width: usize,
+    line_len: usize,
+    prev_cr: bool,
+    line_stats: LineStats,
+    options: CountOptions,
+    // 単語の境界は改行の前後で必ず区切られるので、1行ずつ溜めて分割する
+    // 長い行はline_limitを超えるたびに確定した部分を数えて捨てる
+    line: String,
+    line_limit: usize,
+    // 区切れなかった行を次に区切ろうとする長さ(line_limitより短ければline_limit)
+    flush_at: usize,
+    // 溜めている行の先頭の単語が、数えて捨てた単語の続きか
+    word_continues: bool,
+    // 終わりがまだ分からない書記素クラスタ
+    cluster: String,
+}
+
+impl Counter {
+    fn new(options: CountOptions) -> Self {
+        Counter {
+            options,
+            line_limit: LINE_LIMIT,
+            ..Default::default()
+        }
+    }
+
+    fn feed_str(&mut self, text: &str) {
+        for c in text.chars() {
+            self.feed_char(c);
+        }
+        if let Some(&last) = text.as_bytes().last() {
+            self.last_byte = Some(last);
+        }
+    }
+
+    // 不正なバイト列はString::from_utf8_lossyと同じく1つのU+FFFDとして数える
+    fn feed_invalid(&mut self, bytes: &[u8]) {
+        self.feed_char(char::REPLACEMENT_CHARACTER);
+        self.last_byte = bytes.last().copied();
+    }
+
+    fn feed_char(&mut self, c: char) {
+        self.num_chars += 1;
+        if c == '\n' {
+            self.num_lines += 1;
+            if self.prev_cr {
+                self.num_crlf += 1;
+            }
+        }
+        if self.options.words_mode == WordsMode::Whitespace {
+            if c.is_whitespace() {
+                self.in_word = false;
+            } else if !self.in_word {
+                self.in_word = true;
+                self.num_words += 1;
+            }
+        }
+        if self.options.words_mode == WordsMode::Unicode {
+            self.line.push(c);
+            if c == '\n' {
+                self.count_words(self.line.len());
+            } else if self.line.len() >= self.line_limit.max(self.flush_at) {
+                self.flush_words();
+            }
+        }
+        if self.options.graphemes {
+            self.feed_grapheme(c);
+        }
+
+        // 行の長さは表示幅で数える(全角文字は2、制御文字は0)
+        match c {
+            '\n' | '\r' | '\x0c' => self.line_width = 0,
+            '\t' => self.line_width += TAB_WIDTH - self.line_width % TAB_WIDTH,
+            _ => self.line_width += c.width().unwrap_or(0),
+        }
+        self.num_max_line = self.num_max_line.max(self.line_width);
+
+        if self.options.stats {
+            if c == '\n' {
+                // CRLFのCRも行の長さに含めない
+                self.line_stats
+                    .add(self.line_len - usize::from(self.prev_cr));
+                self.line_len = 0;
+            } else {
+                self.line_len += 1;
+            }
+        }
+        self.prev_cr = c == '\r';
+    }
+
+    // 溜めている行のうち、後に続く文字で分割が変わらない部分の単語を数えて捨てる
+    fn flush_words(&mut self) {
+        // 境界を決めるための先読みは次の区間の中で終わるので、
+        // 最後の2つの区間より前の境界は確定している
+        let bounds: Vec<usize> = self
+            .line
+            .split_word_bound_indices()
+            .map(|(i, _)| i)
+            .collect();
+        if bounds.len() >= 3 {
+            self.count_words(bounds[bounds.len() - 2]);
+        }
+        // 空白や記号のない長い単語は、単語の途中で切って続きとして数える
+        if let Some(cut) = self.word_cut() {
+            self.count_words(cut);
+            self.word_continues = true;
+        }
+        // 空白の並びの中から単語は始まらないので、最後の区間が単語を含まない空白の並びなら、
+        // 続く文字との境界を決める最後の1文字だけを残す
+        if let Some((start, segment)) = self.line.split_word_bound_indices().next_back() {
+            if segment.starts_with(char::is_whitespace) && segment.unicode_words().next().is_none()
+            {
+                let end = self.line.len() - segment.chars().next_back().map_or(0, char::len_utf8);
+                if end > start {
+                    self.count_words(end);
+                }
+            }
+        }
+        // 区切れない行を毎回分割し直さないように、次に区切る長さを延ばす
+        self.flush_at = self.line.len() * 2;
+    }
+
+    // 単語の途中で、前後の文字がどちらも単語の文字である最後の位置
+    // 後の文字がExtend、Format、ZWJのように境界の判定で無視される文字でなければ、
+    // 規則が見返すのは間の記号と無視される文字を挟んでその文字までなので、
+    // ここから後を新しい行として分割しても同じ結果になる
+    fn word_cut(&self) -> Option<usize> {
+        let mut chars = self.line.char_indices().rev();
+        let (mut pos, mut next) = chars.next()?;
+        for (i, c) in chars {
+            if joins_words(c, next) {
+                return Some(pos);
+            }
+            (pos, next) = (i, c);
+        }
+        None
+    }
+
+    // 溜めている行のendまでの単語を数えて捨てる
+    // endは単語の境界か、word_cutで見つけた位置
+    fn count_words(&mut self, end: usize) {
+        let mut words = self.line[..end].unicode_word_indices().peekable();
+        let continued = self.word_continues && words.peek().is_some_and(|&(i, _)| i == 0);
+        self.num_words += words.count() - usize::from(continued);
+        self.line.drain(..end);
+        self.word_continues = false;
+        self.flush_at = 0;
+    }
+
+    // 書記素クラスタは終わりが分かった時点で数え、続きの分からないクラスタだけを持つ
+    fn feed_grapheme(&mut self, c: char) {
+        let start = self.cluster.len();
+        self.cluster.push(c);
+        let mut cursor = GraphemeCursor::new(start, self.cluster.len(), true);
+        if start > 0 && cursor.is_boundary(&self.cluster, 0).unwrap_or(true) {
+            self.num_graphemes += 1;
+            self.cluster.drain(..start);
+        }
+    }
+
+    // POSIXのwcと同じく改行文字の数を行数とする
+    // count_unterminatedの場合は改行で終わっていない最後の行も1行として数える
+    fn finish(mut self) -> FileInfo {
+        self.count_words(self.line.len());
+        self.num_graphemes += usize::from(!self.cluster.is_empty());
+        let unterminated = matches!(self.last_byte, Some(b) if b != b'\n');
+        if unterminated {
+            self.line_stats.add(self.line_len);
+        }
+        let num_unterminated = usize::from(unterminated && self.options.count_unterminated);
+        FileInfo {
+            num_lines: self.num_lines + num_unterminated,
+            num_crlf: self.num_crlf,
+            num_lf: self.num_lines - self.num_crlf,
+            num_words: self.num_words,
+            num_bytes: self.num_bytes,
+            num_chars: self.num_chars,
+            num_graphemes: self.num_graphemes,
+            num_max_line: self.num_max_line,
+            line_stats: self.options.stats.then_some(self.line_stats),
+        }
+    }
+}
+
+// aとbがどちらも単語の文字で同じ単語になり、bが境界の判定で無視される文字でないならtrue
+fn joins_words(a: char, b: char) -> bool {
+    let is_word = |c: char| {
+        c.encode_utf8(&mut [0; MAX_UTF8_LEN])
+            .unicode_words()
+            .count()
+            == 1
+    };
+    // 無視される文字なら前の"."と同じ区間になる
+    is_word(a)
+        && is_word(b)
+        && format!("{a}{b}").split_word_bounds().count() == 1
+        && format!(".{b}").split_word_bounds().count() == 2
+}
+
+pub fn count(file: impl Read) -> io::Result<FileInfo> {
+    count_with(file, CountOptions::default())
+}
+
+// 内部でまとめて読み込むのでBufReadでなくてよい
+pub fn count_with(file: impl Read, options: CountOptions) -> io::Result<FileInfo> {
+    count_chunks(file, options, CHUNK_SIZE)
+}
+
+fn count_chunks(file: impl Read, options: CountOptions, chunk_size: usize) -> io::Result<FileInfo> {
+    count_with_counter(file, Counter::new(options), chunk_size)
+}
+
+// ファイル全体をメモリに読み込まず、chunk_sizeバイトずつ数え上げる
+fn count_with_counter(
+    mut file: impl Read,
+    mut counter: Counter,
+    chunk_size: usize,
+) -> io::Result<FileInfo> {
+    // 先頭には前のチャンクから持ち越した途中までの文字が入る
+    let mut buf = vec![0u8; MAX_UTF8_LEN + chunk_size];
+    let mut carry = 0;
+
+    loop {
+        let bytes_read = match file.read(&mut buf[carry..carry + chunk_size]) {
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if bytes_read == 0 {
+            break;
+        }
+        counter.num_bytes += bytes_read;
+
+        let len = carry + bytes_read;
+        let mut data = &buf[..len];
+        carry = 0;
+        loop {
+            match str::from_utf8(data) {
+                Ok(text) => {
+                    counter.feed_str(text);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, rest) = data.split_at(e.valid_up_to());
+                    // SAFETY: valid_up_to()までは正しいUTF-8であることが検証済み
+                    counter.feed_str(unsafe { str::from_utf8_unchecked(valid) });
+                    match e.error_len() {
+                        Some(invalid_len) => {
+                            counter.feed_invalid(&rest[..invalid_len]);
+                            data = &rest[invalid_len..];
+                        }
+                        None => {
+                            // 文字の途中でチャンクが終わったので次のチャンクに持ち越す
+                            carry = rest.len();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        buf.copy_within(len - carry..len, 0);
+    }
+
+    // 途中で終わった文字が残っていれば不正なバイト列として数える
+    if carry > 0 {
+        counter.feed_invalid(&buf[..carry]);
+    }
+    Ok(counter.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        count, count_chunks, count_with, count_with_counter, CountOptions, Counter, FileInfo,
+        WordsMode,
+    };
+    use proptest::prelude::*;
+    use std::io::{BufRead, Cursor};
+    use unicode_segmentation::UnicodeSegmentation;
+    use unicode_width::UnicodeWidthChar;
+
+    // ファイル全体を読み込んで数える以前の実装(行数は改行文字の数に変更)
+    // チャンク単位の実装と結果を比較するために使う
+    fn count_whole(mut file: impl BufRead) -> FileInfo {
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes).unwrap();
+        let utf8_bytes = String::from_utf8_lossy(&bytes);
+
+        FileInfo {
+            num_bytes: utf8_bytes.bytes().count(),
+            num_chars: utf8_bytes.chars().count(),
+            num_lines: utf8_bytes.matches('\n').count(),
+            num_crlf: utf8_bytes.matches("\r\n").count(),
+            num_lf: utf8_bytes.matches('\n').count() - utf8_bytes.matches("\r\n").count(),
+            num_words: utf8_bytes.split_whitespace().count(),
+            num_graphemes: 0,
+            num_max_line: max_line_width(&utf8_bytes),
+            line_stats: None,
+        }
+    }
+
+    #[test]
+    fn test_count() {
+        let text = "I don't want the world. I just want your half.\r\n";
+        let info = count(Cursor::new(text));
+        assert!(info.is_ok());
+        let expected = FileInfo {
+            num_bytes: 48,
+            num_chars: 48,
+            num_lines: 1,
+            num_crlf: 1,
+            num_lf: 0,
+            num_words: 10,
+            num_graphemes: 0,
+            num_max_line: 46,
+            line_stats: None,
+        };
+        assert_eq!(info.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_count_chunk_boundaries() {
+        // 単語、マルチバイト文字、CRLFがチャンクの境界をまたぐ
+        let text = "Frétt hefir öld óvu,\r\n þá er  endr of gerðu\nseggir";
+        let expected = count_whole(Cursor::new(text));
+        for chunk_size in 1..=8 {
+            let info =
+                count_chunks(Cursor::new(text), CountOptions::default(), chunk_size).unwrap();
+            assert_eq!(info, expected, "chunk_size = {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn test_count_invalid_utf8() {
+        // 途中で切れた文字や不正なバイトは1文字として数える
+        let bytes = b"ab\xe3\x81 \xff\xfe\n\xe3\x81";
+        for chunk_size in 1..=4 {
+            let info =
+                count_chunks(Cursor::new(bytes), CountOptions::default(), chunk_size).unwrap();
+            let expected = FileInfo {
+                num_bytes: 10,
+                num_chars: 8,
+                num_lines: 1,
+                num_crlf: 0,
+                num_lf: 1,
+                num_words: 3,
+                num_graphemes: 0,
+                num_max_line: 6,
+                line_stats: None,
+            };
+            assert_eq!(info, expected, "chunk_size = {chunk_size}");
+        }
+    }
+
+    // 行を区切ってから幅を数える、チャンク単位の実装とは独立した計算
+    fn max_line_width(text: &str) -> usize {
+        text.split(['\n', '\r', '\x0c'])
+            .map(|line| {
+                line.chars().fold(0, |width, c| match c {
+                    '\t' => width + 8 - width % 8,
+                    _ => width + c.width().unwrap_or(0),
+                })
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_count_max_line() {
+        let text = "日本語\nab\tc\r\n全角　空白\tx\n";
+        let info = count(Cursor::new(text)).unwrap();
+        assert_eq!(info.num_max_line, 17);
+
+        let info = count(Cursor::new("")).unwrap();
+        assert_eq!(info.num_max_line, 0);
+
+        // 制御文字の幅は0、CRで行頭に戻る
+        let info = count(Cursor::new("abcd\rab\x07\n")).unwrap();
+        assert_eq!(info.num_max_line, 4);
+    }
+
+    const UNICODE: CountOptions = CountOptions {
+        words_mode: WordsMode::Unicode,
+        graphemes: true,
+        stats: false,
+        count_unterminated: false,
+    };
+
+    const STATS: CountOptions = CountOptions {
+        words_mode: WordsMode::Whitespace,
+        graphemes: false,
+        stats: true,
+        count_unterminated: false,
+    };
+
+    const UNTERMINATED: CountOptions = CountOptions {
+        words_mode: WordsMode::Whitespace,
+        graphemes: false,
+        stats: false,
+        count_unterminated: true,
+    };
+
+    #[test]
+    fn test_count_lines() {
+        // 改行で終わっていない最後の行は既定では数えない
+        let text = "one\ntwo\r\nthree";
+        let info = count(Cursor::new(text)).unwrap();
+        assert_eq!(info.num_lines, 2);
+        assert_eq!(info.num_crlf, 1);
+        assert_eq!(info.num_lf, 1);
+
+        let info = count_with(Cursor::new(text), UNTERMINATED).unwrap();
+        assert_eq!(info.num_lines, 3);
+        assert_eq!(info.num_crlf, 1);
+        assert_eq!(info.num_lf, 1);
+
+        // 改行で終わっていれば同じ
+        let info = count_with(Cursor::new("one\r\r\n\n"), UNTERMINATED).unwrap();
+        assert_eq!(info.num_lines, 2);
+        assert_eq!(info.num_crlf, 1);
+        assert_eq!(info.num_lf, 1);
+    }
+
+    // 行ごとに分割してから数える、チャンク単位の実装とは独立した計算
+    fn line_lengths(text: &str) -> Vec<usize> {
+        text.lines().map(|line| line.chars().count()).collect()
+    }
+
+    #[test]
+    fn test_line_stats() {
+        let text = "abc\n\nabcdefgh\r\nabcd\nab";
+        let info = count_with(Cursor::new(text), STATS).unwrap();
+        let stats = info.line_stats.unwrap();
+        assert_eq!(stats.num_lines(), 5);
+        assert_eq!(stats.num_empty(), 1);
+        assert_eq!(stats.min(), Some(0));
+        assert_eq!(stats.max(), Some(8));
+        assert_eq!(stats.mean(), Some(3.4));
+        assert_eq!(stats.median(), Some(3.0));
+        assert_eq!(
+            stats.histogram(),
+            vec![(0, 0, 1), (1, 1, 0), (2, 3, 2), (4, 7, 1), (8, 15, 1)]
+        );
+
+        // 偶数行の中央値は中央の2つの平均
+        let info = count_with(Cursor::new("a\nabcd\n"), STATS).unwrap();
+        let stats = info.line_stats.unwrap();
+        assert_eq!(stats.median(), Some(2.5));
+        assert_eq!(stats.histogram(), vec![(1, 1, 1), (2, 3, 0), (4, 7, 1)]);
+
+        let info = count_with(Cursor::new(""), STATS).unwrap();
+        let stats = info.line_stats.unwrap();
+        assert_eq!(stats.num_lines(), 0);
+        assert_eq!(stats.min(), None);
+        assert_eq!(stats.mean(), None);
+        assert_eq!(stats.median(), None);
+        assert_eq!(stats.histogram(), vec![]);
+
+        let info = count(Cursor::new(text)).unwrap();
+        assert_eq!(info.line_stats, None);
+    }
+
+    #[test]
+    fn test_sum() {
+        let texts = ["abc\n\n", "abcdefgh\r\nabcd\n", "", "a b\nab"];
+        let infos: Vec<FileInfo> = texts
+            .iter()
+            .map(|text| count_with(Cursor::new(text), STATS).unwrap())
+            .collect();
+        let total: FileInfo = infos.iter().sum();
+        // 全てをつなげたものを数えた結果と同じになる
+        let all = count_with(Cursor::new(texts.concat()), STATS).unwrap();
+        assert_eq!(total.num_lines, all.num_lines);
+        assert_eq!(total.num_crlf, all.num_crlf);
+        assert_eq!(total.num_lf, all.num_lf);
+        assert_eq!(total.num_words, all.num_words);
+        assert_eq!(total.num_bytes, all.num_bytes);
+        assert_eq!(total.num_chars, all.num_chars);
+        assert_eq!(total.num_max_line, all.num_max_line);
+        assert_eq!(total.line_stats, all.line_stats);
+        assert_eq!(infos.into_iter().sum::<FileInfo>(), total);
+
+        let empty: [FileInfo; 0] = [];
+        assert_eq!(empty.into_iter().sum::<FileInfo>(), FileInfo::default());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let info = count_with(Cursor::new("abc\n\nabcd\r\n"), STATS).unwrap();
+        let json = serde_json::to_string(&info).unwrap();
+        let parsed: FileInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, info);
+    }
+
+    #[test]
+    fn test_count_unicode_words() {
+        // 空白のない日本語もUAX #29の単語境界で区切る
+        let text = "吾輩は猫である。\nカタカナ語とEnglish words.\n";
+        let info = count(Cursor::new(text)).unwrap();
+        assert_eq!(info.num_words, 3);
+
+        let info = count_with(Cursor::new(text), UNICODE).unwrap();
+        assert_eq!(info.num_words, 12);
+        assert_eq!(info.num_chars, 30);
+        assert_eq!(info.num_graphemes, 30);
+    }
+
+    #[test]
+    fn test_count_graphemes() {
+        // 濁点の結合文字やCRLFは1つの書記素クラスタになる
+        let text = "か\u{3099}き\u{3099}\r\ne\u{301}";
+        let info = count_with(Cursor::new(text), UNICODE).unwrap();
+        assert_eq!(info.num_chars, 8);
+        assert_eq!(info.num_graphemes, 4);
+        assert_eq!(info.num_words, 3);
+
+        let info = count(Cursor::new(text)).unwrap();
+        assert_eq!(info.num_graphemes, 0);
+    }
+
+    #[test]
+    fn test_count_unicode_long_line() {
+        // 空白のない長い行や長い単語でも、溜める長さはline_limitの2倍程度に収まる
+        for unit in [
+            "{\"key\":[1,2.5,\"v\"]},",
+            "abcdefgh",
+            "1,000.5",
+            "か\u{3099}",
+            " ",
+            "\t",
+        ] {
+            let text = unit.repeat(10_000);
+            let mut counter = Counter {
+                line_limit: 64,
+                ..Counter::new(UNICODE)
+            };
+            let mut max_len = 0;
+            for c in text.chars() {
+                counter.feed_char(c);
+                max_len = max_len.max(counter.line.len()).max(counter.cluster.len());
+            }
+            assert!(max_len <= 2 * 64, "{unit}: {max_len}");
+            let info = counter.finish();
+            assert_eq!(info.num_words, text.unicode_words().count(), "{unit}");
+            assert_eq!(info.num_graphemes, text.graphemes(true).count(), "{unit}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn prop_count_matches_whole(
+            text in "(\\PC|\\s){0,200}",
+            chunk_size in 1usize..16,
+        ) {
+            let info = count_chunks(Cursor::new(&text), CountOptions::default(), chunk_size).unwrap();
+            prop_assert_eq!(info, count_whole(Cursor::new(&text)));
+        }
+
+        #[test]
+        fn prop_count_matches_whole_bytes(
+            bytes in proptest::collection::vec(any::<u8>(), 0..200),
+            chunk_size in 1usize..16,
+        ) {
+            // 以前の実装は置換後のU+FFFDのバイト数を数えていたので、
+            // バイト数だけは入力そのものの長さと比較する
+            let info = count_chunks(Cursor::new(&bytes), CountOptions::default(), chunk_size).unwrap();
+            let expected = FileInfo {
+                num_bytes: bytes.len(),
+                ..count_whole(Cursor::new(&bytes))
+            };
+            prop_assert_eq!(info, expected);
+        }
+
+        #[test]
+        fn prop_count_unterminated_matches_split_terminator(
+            text in "(\\PC|\\s){0,200}",
+            chunk_size in 1usize..16,
+        ) {
+            let info = count_chunks(Cursor::new(&text), UNTERMINATED, chunk_size).unwrap();
+            prop_assert_eq!(info.num_lines, text.split_terminator('\n').count());
+        }
+
+        #[test]
+        fn prop_count_unicode_matches_whole(
+            text in "(\\PC|\\s){0,200}",
+            chunk_size in 1usize..16,
+        ) {
+            let info = count_chunks(Cursor::new(&text), UNICODE, chunk_size).unwrap();
+            prop_assert_eq!(info.num_words, text.unicode_words().count());
+            prop_assert_eq!(info.num_graphemes, text.graphemes(true).count());
+        }
+
+        #[test]
+        fn prop_count_unicode_long_lines(
+            text in "([a-zあ0-9.,:'_ \\t\\r\\n\u{3099}\u{301}\u{903}\u{ad}\u{200b}\u{200d}\u{1f44d}\u{1f3fd}\u{1f1ef}\u{ff76}\u{ff9e}\u{5d0}\"]|\\PC){0,200}",
+            chunk_size in 1usize..16,
+            line_limit in 0usize..8,
+        ) {
+            // 行を途中で区切っても、行全体を分割した場合と同じ数になる
+            let counter = Counter {
+                line_limit,
+                ..Counter::new(UNICODE)
+            };
+            let info = count_with_counter(Cursor::new(&text), counter, chunk_size).unwrap();
+            prop_assert_eq!(info.num_words, text.unicode_words().count());
+            prop_assert_eq!(info.num_graphemes, text.graphemes(true).count());
+        }
+
+        #[test]
+        fn prop_line_stats_matches_lines(
+            text in "([a-zあ-ん\\r ]{0,10}\\n?){0,20}",
+            chunk_size in 1usize..16,
+        ) {
+            let info = count_chunks(Cursor::new(&text), STATS, chunk_size).unwrap();
+            let stats = info.line_stats.unwrap();
+            let lengths = line_lengths(&text);
+            prop_assert_eq!(stats.num_lines(), lengths.len());
+            prop_assert_eq!(stats.min(), lengths.iter().min().copied());
+            prop_assert_eq!(stats.max(), lengths.iter().max().copied());
+            prop_assert_eq!(
+                stats.num_empty(),
+                lengths.iter().filter(|&&len| len == 0).count()
+            );
+        }
+    }
+}
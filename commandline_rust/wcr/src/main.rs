@@ -1,13 +1,25 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, ValueEnum};
+use libflate::gzip::MultiDecoder;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use wcr::{count_with, CountOptions, FileInfo, LineStats, WordsMode};
+use xz2::bufread::XzDecoder;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
     #[arg(default_value = "-", value_name = "FILES")]
-    files: Vec<String>,
+    files: Vec<PathBuf>,
 
     #[arg(short, long)]
     lines: bool,
@@ -20,16 +32,66 @@ struct Args {
 
     #[arg(short('m'), long, conflicts_with("bytes"))]
     chars: bool,
+
+    #[arg(long)]
+    graphemes: bool,
+
+    #[arg(short('L'), long)]
+    max_line_length: bool,
+
+    #[arg(long, value_name = "F", conflicts_with("files"))]
+    files0_from: Option<PathBuf>,
+
+    // 0の場合は利用可能なCPU数
+    #[arg(short, long, value_name = "N", default_value = "1")]
+    jobs: usize,
+
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+
+    #[arg(long, value_enum, default_value_t = WordsModeArg::Whitespace)]
+    words_mode: WordsModeArg,
+
+    #[arg(long)]
+    stats: bool,
+
+    #[arg(long)]
+    count_unterminated: bool,
+
+    #[arg(long)]
+    crlf: bool,
+
+    // 圧縮されたファイルを展開せずにそのまま数える
+    #[arg(long)]
+    raw: bool,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct FileInfo {
-    num_lines: usize,
-    num_words: usize,
-    num_bytes: usize,
-    num_chars: usize,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Csv,
 }
 
+// ライブラリがclapに依存しないように、--words-modeの値はここで対応付ける
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum WordsModeArg {
+    Whitespace,
+    Unicode,
+}
+
+impl From<WordsModeArg> for WordsMode {
+    fn from(mode: WordsModeArg) -> Self {
+        match mode {
+            WordsModeArg::Whitespace => WordsMode::Whitespace,
+            WordsModeArg::Unicode => WordsMode::Unicode,
+        }
+    }
+}
+
+// table形式の列の最小幅
+const MIN_COLUMN_WIDTH: usize = 8;
+
 fn main() {
     if let Err(e) = run(Args::parse()) {
         eprintln!("{}", e);
@@ -39,104 +101,498 @@ fn main() {
 
 fn run(mut args: Args) -> Result<()> {
     // 全てのフラグがFalseだったらlines, words, bytesをTrueにする
-    if !args.lines && !args.words && !args.bytes && !args.chars {
+    if !args.lines
+        && !args.words
+        && !args.bytes
+        && !args.chars
+        && !args.graphemes
+        && !args.max_line_length
+        && !args.crlf
+    {
         args.lines = true;
         args.words = true;
         args.bytes = true;
     }
 
-    let mut total_bytes = 0usize;
-    let mut total_chars = 0usize;
-    let mut total_lines = 0usize;
-    let mut total_words = 0usize;
-    for filename in &args.files {
-        match open(filename) {
-            Err(e) => eprintln!("{}: {}", filename, e),
-            Ok(file) => {
-                let file_info = count(file)?;
-                if args.lines {
-                    total_lines += file_info.num_lines;
-                    print!("{:>8}", file_info.num_lines);
-                }
-                if args.words {
-                    total_words += file_info.num_words;
-                    print!("{:>8}", file_info.num_words);
-                }
-                if args.bytes {
-                    total_bytes += file_info.num_bytes;
-                    print!("{:>8}", file_info.num_bytes);
+    let files = match &args.files0_from {
+        Some(list) => read_files0(list).map_err(|e| anyhow!("{}: {}", list.display(), e))?,
+        None => args.files.clone(),
+    };
+    let jobs = match args.jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    let files0_stdin = args
+        .files0_from
+        .as_ref()
+        .is_some_and(|list| list.as_os_str() == "-");
+    if args.stats && args.format == Format::Csv {
+        bail!("--stats cannot be used with --format csv");
+    }
+    // 書記素クラスタは数えるのに時間がかかるので、json, csvでも--graphemesのときだけ数える
+    let options = CountOptions {
+        words_mode: args.words_mode.into(),
+        graphemes: args.graphemes,
+        stats: args.stats,
+        count_unterminated: args.count_unterminated,
+    };
+
+    // どの形式も数え終えたファイルから引数の順番どおりに出力し、
+    // エラーメッセージも同じ順番で間に出力する
+    let width = column_width(&files);
+    let mut stats = vec![];
+    let mut total = FileInfo::default();
+    let mut read_failed = false;
+    let mut csv_writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(io::stdout());
+    if args.format == Format::Csv {
+        write_csv_header(&mut csv_writer)?;
+    }
+    count_files(
+        &files,
+        files0_stdin,
+        args.raw,
+        options,
+        jobs,
+        |filename, counted| {
+            let mut file_info = match counted {
+                Ok(file_info) => file_info,
+                Err(e) => {
+                    read_failed |= matches!(e, FileError::Read(_));
+                    eprintln!("{}", e);
+                    return Ok(());
                 }
-                if args.chars {
-                    total_chars += file_info.num_chars;
-                    print!("{:>8}", file_info.num_chars);
+            };
+            match args.format {
+                Format::Table => {
+                    print_row(&columns(&args, &file_info), width, filename);
+                    // table形式の統計は表の後にまとめて出力する
+                    if let Some(line_stats) = file_info.line_stats.take() {
+                        stats.push((filename.to_path_buf(), line_stats));
+                    }
                 }
-                if filename != "-" {
-                    print!(" {filename}")
+                Format::Json => {
+                    print_json(&Record::new(Some(filename), &file_info, args.graphemes))?
                 }
-                println!("");
+                Format::Csv => write_csv(
+                    &mut csv_writer,
+                    &Record::new(Some(filename), &file_info, args.graphemes),
+                )?,
+            }
+            // 行の長さの統計はファイルごとにだけ出力する
+            total = mem::take(&mut total)
+                + FileInfo {
+                    line_stats: None,
+                    ..file_info
+                };
+            Ok(())
+        },
+    )?;
+
+    match args.format {
+        Format::Table => {
+            if files.len() >= 2 {
+                print_row(&columns(&args, &total), width, Path::new("total"));
+            }
+            for (filename, line_stats) in &stats {
+                print_stats(filename, line_stats);
             }
         }
+        Format::Json => print_json(&Record::new(None, &total, args.graphemes))?,
+        Format::Csv => write_csv(&mut csv_writer, &Record::new(None, &total, args.graphemes))?,
     }
-    if args.files.len() >= 2 {
-        if args.lines {
-            print!("{:>8}", total_lines);
-        }
-        if args.words {
-            print!("{:>8}", total_words);
-        }
-        if args.bytes {
-            print!("{:>8}", total_bytes);
+    // 読み込みの途中で失敗したファイルがあれば、全て出力し終えてから失敗として終了する
+    if read_failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+// 選択されたフラグの順に値を並べる
+fn columns(args: &Args, file_info: &FileInfo) -> Vec<usize> {
+    [
+        (args.lines, file_info.num_lines),
+        (args.words, file_info.num_words),
+        (args.bytes, file_info.num_bytes),
+        (args.chars, file_info.num_chars),
+        (args.graphemes, file_info.num_graphemes),
+        (args.max_line_length, file_info.num_max_line),
+        (args.crlf, file_info.num_crlf),
+        (args.crlf, file_info.num_lf),
+    ]
+    .into_iter()
+    .filter_map(|(selected, value)| selected.then_some(value))
+    .collect()
+}
+
+// table形式の列の幅
+// 行を数え終えたファイルから出力できるように、GNU wcと同じく数える前にファイルの大きさから決める
+// 標準入力や通常のファイル以外は大きさが分からないので、最小幅のまま数える
+fn column_width(files: &[PathBuf]) -> usize {
+    let size: u64 = files
+        .iter()
+        .filter(|filename| filename.as_os_str() != "-")
+        .filter_map(|filename| filename.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum();
+    (size.to_string().len() + 1).max(MIN_COLUMN_WIDTH)
+}
+
+// 圧縮されたファイルのように値がファイルの大きさより大きくなっても、値の前に必ず空白を入れる
+fn print_row(values: &[usize], width: usize, filename: &Path) {
+    for value in values {
+        print!(" {:>width$}", value, width = width - 1);
+    }
+    if filename.as_os_str() != "-" {
+        print!(" {}", filename.display())
+    }
+    println!();
+}
+
+// 統計の棒グラフの最大の長さ
+const HISTOGRAM_WIDTH: usize = 40;
+
+fn print_stats(filename: &Path, stats: &LineStats) {
+    println!();
+    println!("{}:", filename.display());
+    println!(
+        "  lines: {}, empty: {}",
+        stats.num_lines(),
+        stats.num_empty()
+    );
+    if let (Some(min), Some(max), Some(mean), Some(median)) =
+        (stats.min(), stats.max(), stats.mean(), stats.median())
+    {
+        println!("  min: {min}, max: {max}, mean: {mean:.2}, median: {median}");
+    }
+
+    let histogram = stats.histogram();
+    let most = histogram
+        .iter()
+        .map(|&(_, _, count)| count)
+        .max()
+        .unwrap_or(0);
+    let ranges: Vec<String> = histogram
+        .iter()
+        .map(|&(low, high, _)| {
+            if low == high {
+                low.to_string()
+            } else {
+                format!("{low}-{high}")
+            }
+        })
+        .collect();
+    let range_width = ranges.iter().map(String::len).max().unwrap_or(0);
+    let count_width = most.to_string().len();
+    for (range, &(_, _, count)) in ranges.iter().zip(&histogram) {
+        let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(most));
+        let row = format!("  {range:>range_width$} | {count:>count_width$} {bar}");
+        println!("{}", row.trim_end());
+    }
+}
+
+// json, csvの1行分
+// csvでは行の長さの統計を出力しない
+#[derive(Serialize)]
+struct Record<'a> {
+    // 合計の行はnull(csvでは空欄)にして、"total"という名前のファイルと区別する
+    // UTF-8として正しくないファイル名はU+FFFDに置き換える
+    file: Option<Cow<'a, str>>,
+    lines: usize,
+    words: usize,
+    bytes: usize,
+    chars: usize,
+    // 書記素クラスタを数えていなければnull(csvでは空欄)
+    graphemes: Option<usize>,
+    max_line_length: usize,
+    crlf_lines: usize,
+    lf_lines: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<StatsRecord>,
+}
+
+impl<'a> Record<'a> {
+    fn new(filename: Option<&'a Path>, file_info: &FileInfo, graphemes: bool) -> Self {
+        Record {
+            file: filename.map(Path::to_string_lossy),
+            lines: file_info.num_lines,
+            words: file_info.num_words,
+            bytes: file_info.num_bytes,
+            chars: file_info.num_chars,
+            graphemes: graphemes.then_some(file_info.num_graphemes),
+            max_line_length: file_info.num_max_line,
+            crlf_lines: file_info.num_crlf,
+            lf_lines: file_info.num_lf,
+            stats: file_info.line_stats.as_ref().map(StatsRecord::new),
         }
-        if args.chars {
-            print!("{:>8}", total_chars);
+    }
+}
+
+#[derive(Serialize)]
+struct StatsRecord {
+    lines: usize,
+    empty: usize,
+    min: Option<usize>,
+    max: Option<usize>,
+    mean: Option<f64>,
+    median: Option<f64>,
+    histogram: Vec<Bucket>,
+}
+
+impl StatsRecord {
+    fn new(stats: &LineStats) -> Self {
+        StatsRecord {
+            lines: stats.num_lines(),
+            empty: stats.num_empty(),
+            min: stats.min(),
+            max: stats.max(),
+            mean: stats.mean(),
+            median: stats.median(),
+            histogram: stats
+                .histogram()
+                .into_iter()
+                .map(|(min, max, count)| Bucket { min, max, count })
+                .collect(),
         }
-        println!(" total");
     }
+}
+
+#[derive(Serialize)]
+struct Bucket {
+    min: usize,
+    max: usize,
+    count: usize,
+}
+
+// 1行に1つのJSONオブジェクトを出力する(JSON Lines)
+fn print_json(record: &Record) -> Result<()> {
+    println!("{}", serde_json::to_string(record)?);
+    Ok(())
+}
+
+// ヘッダはエラーメッセージより先に出力しておく
+fn write_csv_header(wtr: &mut csv::Writer<impl Write>) -> Result<()> {
+    wtr.write_record([
+        "file",
+        "lines",
+        "words",
+        "bytes",
+        "chars",
+        "graphemes",
+        "max_line_length",
+        "crlf_lines",
+        "lf_lines",
+    ])?;
+    wtr.flush()?;
     Ok(())
 }
 
-fn open(filename: &str) -> Result<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+// エラーメッセージと順番が入れ替わらないように1行ごとに書き出す
+fn write_csv(wtr: &mut csv::Writer<impl Write>, record: &Record) -> Result<()> {
+    wtr.serialize(record)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+// 数えられなかったファイルのエラーメッセージ
+enum FileError {
+    // 開けなかったファイルは飛ばして、終了コードは変えない
+    Open(String),
+    // 読み込みの途中で失敗したファイルも飛ばすが、最後に終了コードを1にする
+    Read(String),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileError::Open(e) | FileError::Read(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// 数えられなかった場合はファイルごとのエラーを返し、残りのファイルは続けて数える
+fn count_file(
+    filename: &Path,
+    files0_stdin: bool,
+    raw: bool,
+    options: CountOptions,
+) -> Result<FileInfo, FileError> {
+    if filename.as_os_str().is_empty() {
+        return Err(FileError::Open("invalid zero-length file name".to_string()));
+    }
+    // 標準入力からファイル名を読んでいる場合は"-"を指定できない
+    if filename.as_os_str() == "-" && files0_stdin {
+        return Err(FileError::Open(
+            "when reading file names from stdin, no file name of '-' allowed".to_string(),
+        ));
     }
+    let file = match open(filename, raw) {
+        Ok(file) => file,
+        Err(e) => return Err(FileError::Open(format!("{}: {}", filename.display(), e))),
+    };
+    // 圧縮データが途中で壊れている場合もここで失敗する
+    count_with(file, options).map_err(|e| FileError::Read(format!("{}: {}", filename.display(), e)))
 }
 
-pub fn count(file: impl BufRead) -> Result<FileInfo> {
-    // 1行ずつ読み込んで数え上げないと巨大なファイルを扱えないかも？
-    // read_lineで一行ずつ読み込んで数え上げようとしてもファイルが一行だったらだめ？
-    let bytes = file.bytes().collect::<Result<Vec<_>, _>>()?;
-    let utf8_bytes = String::from_utf8_lossy(&bytes);
+// jobs個のスレッドでファイルを数え上げ、引数の順番どおりにreportに渡す
+// 標準入力は複数のスレッドで同時に読めないので、報告する側のスレッドで順番に数える
+fn count_files(
+    files: &[PathBuf],
+    files0_stdin: bool,
+    raw: bool,
+    options: CountOptions,
+    jobs: usize,
+    mut report: impl FnMut(&Path, Result<FileInfo, FileError>) -> Result<()>,
+) -> Result<()> {
+    if jobs <= 1 {
+        for filename in files {
+            report(filename, count_file(filename, files0_stdin, raw, options))?;
+        }
+        return Ok(());
+    }
 
-    let num_bytes = utf8_bytes.bytes().count();
-    let num_chars = utf8_bytes.chars().count();
-    let num_words = utf8_bytes.split_whitespace().count();
-    let num_lines = utf8_bytes.split_terminator('\n').count();
+    let next = AtomicUsize::new(0);
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..jobs.min(files.len()) {
+            let tx = tx.clone();
+            let next = &next;
+            s.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= files.len() {
+                    break;
+                }
+                if files[i].as_os_str() == "-" {
+                    continue;
+                }
+                // 受信側が先に終了していたら残りは数えない
+                if tx
+                    .send((i, count_file(&files[i], files0_stdin, raw, options)))
+                    .is_err()
+                {
+                    break;
+                }
+            });
+        }
+        drop(tx);
 
-    Ok(FileInfo {
-        num_bytes,
-        num_chars,
-        num_lines,
-        num_words,
+        // 先に終わったファイルは、それより前のファイルが終わるまで取っておく
+        let mut pending = BTreeMap::new();
+        for (i, filename) in files.iter().enumerate() {
+            let counted = if filename.as_os_str() == "-" {
+                count_file(filename, files0_stdin, raw, options)
+            } else {
+                loop {
+                    if let Some(counted) = pending.remove(&i) {
+                        break counted;
+                    }
+                    let (j, counted) = rx.recv()?;
+                    pending.insert(j, counted);
+                }
+            };
+            report(filename, counted)?;
+        }
+        Ok(())
     })
 }
 
-#[cfg(test)]
-mod tests {
-    use super::{count, FileInfo};
-    use std::io::Cursor;
-
-    #[test]
-    fn test_count() {
-        let text = "I don't want the world. I just want your half.\r\n";
-        let info = count(Cursor::new(text));
-        assert!(info.is_ok());
-        let expected = FileInfo {
-            num_bytes: 48,
-            num_chars: 48,
-            num_lines: 1,
-            num_words: 10,
+// Compression::magicの最大の長さ
+const MAX_MAGIC_LEN: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    // 先頭のマジックバイトで判定する。空のファイルや圧縮されていないファイルはNone
+    // 先頭がマジックバイトより短く途中まで一致している場合だけ、拡張子で判定する
+    fn detect(filename: &Path, head: &[u8]) -> Option<Compression> {
+        if let Some(compression) = [Compression::Gzip, Compression::Zstd, Compression::Xz]
+            .into_iter()
+            .find(|compression| head.starts_with(compression.magic()))
+        {
+            return Some(compression);
+        }
+        let compression = match filename.extension()?.to_str()? {
+            "gz" => Compression::Gzip,
+            "zst" => Compression::Zstd,
+            "xz" => Compression::Xz,
+            _ => return None,
         };
-        assert_eq!(info.unwrap(), expected);
+        let magic = compression.magic();
+        (!head.is_empty() && head.len() < magic.len() && magic.starts_with(head))
+            .then_some(compression)
+    }
+
+    fn magic(self) -> &'static [u8] {
+        match self {
+            Compression::Gzip => b"\x1f\x8b",
+            Compression::Zstd => b"\x28\xb5\x2f\xfd",
+            Compression::Xz => b"\xfd7zXZ\x00",
+        }
     }
 }
+
+// rawでなければ圧縮されたファイルを展開しながら読む
+fn open(filename: &Path, raw: bool) -> Result<Box<dyn BufRead>> {
+    let mut file = open_plain(filename)?;
+    if raw {
+        return Ok(file);
+    }
+    // パイプからの1回の読み込みはマジックバイトより短いことがあるので、
+    // 最も長いマジックバイトの長さかEOFまで読んでから判定し、読んだ分は前に戻す
+    let mut head = Vec::with_capacity(MAX_MAGIC_LEN);
+    file.by_ref()
+        .take(MAX_MAGIC_LEN as u64)
+        .read_to_end(&mut head)?;
+    let compression = Compression::detect(filename, &head);
+    let file: Box<dyn BufRead> = Box::new(Cursor::new(head).chain(file));
+    // 複数のストリームを連結したファイルも最後まで展開する
+    Ok(match compression {
+        None => file,
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiDecoder::new(file)?)),
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(file)?)),
+        Some(Compression::Xz) => Box::new(BufReader::new(XzDecoder::new_multi_decoder(file))),
+    })
+}
+
+fn open_plain(filename: &Path) -> io::Result<Box<dyn BufRead>> {
+    if filename.as_os_str() == "-" {
+        Ok(Box::new(BufReader::new(io::stdin())))
+    } else {
+        Ok(Box::new(BufReader::new(File::open(filename)?)))
+    }
+}
+
+// NUL文字で区切られたファイル名の一覧を読み込む
+// 一覧は展開せずにそのまま読む
+fn read_files0(list: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut file = open_plain(list)?;
+    let mut files = vec![];
+    let mut buf = vec![];
+    while file.read_until(b'\0', &mut buf)? != 0 {
+        let name = buf.strip_suffix(b"\0").unwrap_or(&buf);
+        files.push(path_from_bytes(name));
+        buf.clear();
+    }
+    Ok(files)
+}
+
+// Unixではファイル名はバイト列なので、UTF-8として正しくなくてもそのまま使う
+#[cfg(unix)]
+fn path_from_bytes(name: &[u8]) -> PathBuf {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(name))
+}
+
+// それ以外ではUTF-8として正しくない部分を置き換える
+#[cfg(not(unix))]
+fn path_from_bytes(name: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(name).into_owned())
+}
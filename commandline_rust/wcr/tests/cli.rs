@@ -1,14 +1,36 @@
 use anyhow::Result;
-use assert_cmd::Command;
+use assert_cmd::{cargo::cargo_bin, Command};
 use predicates::prelude::*;
 use pretty_assertions::assert_eq;
 use rand::{distributions::Alphanumeric, Rng};
 use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 const PRG: &str = "wcr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const WIDE: &str = "tests/inputs/wide.txt";
+const JAPANESE: &str = "tests/inputs/japanese.txt";
+const GRAPHEMES: &str = "tests/inputs/graphemes.txt";
+const ROWS: &str = "tests/inputs/rows.csv";
+const MIXED: &str = "tests/inputs/mixed.txt";
+const FOX_GZ: &str = "tests/inputs/fox.txt.gz";
+const FOX_ZST: &str = "tests/inputs/fox.txt.zst";
+const FOX_XZ: &str = "tests/inputs/fox.txt.xz";
+const FOX_GZIP: &str = "tests/inputs/fox_gzip";
+const FOX_ATLAMAL_GZ: &str = "tests/inputs/fox_atlamal.txt.gz";
+const BAD_GZ: &str = "tests/inputs/bad.gz";
+const EMPTY_GZ: &str = "tests/inputs/empty.gz";
+const BAD_HEADER_GZ: &str = "tests/inputs/bad_header.gz";
+const TRUNCATED_GZ: &str = "tests/inputs/truncated.gz";
+const FILES0: &str = "tests/inputs/files0.txt";
+const FILES0_BAD: &str = "tests/inputs/files0_bad.txt";
 
 // --------------------------------------------------
 fn gen_bad_file() -> String {
@@ -117,6 +139,30 @@ fn fox_bytes_lines() -> Result<()> {
     run(&["-l", "-c", FOX], "tests/expected/fox.txt.cl.out")
 }
 
+// --------------------------------------------------
+#[test]
+fn fox_max_line_length() -> Result<()> {
+    run(&["--max-line-length", FOX], "tests/expected/fox.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn wide() -> Result<()> {
+    run(&[WIDE], "tests/expected/wide.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_max_line_length() -> Result<()> {
+    run(&["-L", WIDE], "tests/expected/wide.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_lines_chars_max_line_length() -> Result<()> {
+    run(&["-lmL", WIDE], "tests/expected/wide.txt.lmL.out")
+}
+
 // --------------------------------------------------
 #[test]
 fn atlamal() -> Result<()> {
@@ -218,3 +264,634 @@ fn test_all_words_lines() -> Result<()> {
 fn test_all_bytes_lines() -> Result<()> {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn test_all_max_line_length() -> Result<()> {
+    run(
+        &["-L", EMPTY, FOX, ATLAMAL, WIDE],
+        "tests/expected/all.L.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_lines_max_line_length() -> Result<()> {
+    run(&["-lL", EMPTY, FOX, ATLAMAL], "tests/expected/all.lL.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files0_from_and_files() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", FILES0, FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the argument '--files0-from <F>' cannot be used with '[FILES]...'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_files0_from() -> Result<()> {
+    let bad = gen_bad_file();
+    let expected = format!("{bad}: .* [(]os error 2[)]");
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from() -> Result<()> {
+    run(&["--files0-from", FILES0], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_lines() -> Result<()> {
+    run(&["-l", "--files0-from", FILES0], "tests/expected/all.l.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_stdin() -> Result<()> {
+    let input = fs::read(FILES0)?;
+    let expected = fs::read_to_string("tests/expected/all.out")?;
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["--files0-from", "-"])
+        .write_stdin(input)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_bad_entries() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/files0_bad.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", FILES0_BAD])
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predicate::str::contains("invalid zero-length file name"))
+        .stderr(predicate::str::is_match(
+            "tests/inputs/missing.txt: .* [(]os error 2[)]",
+        )?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_stdin_dash() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", "-"])
+        .write_stdin("-\0tests/inputs/fox.txt\0")
+        .assert()
+        .success()
+        .stdout(fs::read_to_string("tests/expected/files0_dash.out")?)
+        .stderr(predicate::str::contains(
+            "when reading file names from stdin, no file name of '-' allowed",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_compressed_list() -> Result<()> {
+    // 一覧のファイルは展開しないので、展開した中身をファイル名として扱わない
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", FOX_GZ])
+        .assert()
+        .stderr(predicate::str::contains("quick brown fox").not());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(unix)]
+fn files0_from_non_utf8_name() -> Result<()> {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    // UTF-8として正しくないファイル名も開ける
+    let dir = tempfile::tempdir()?;
+    let file = dir.path().join(OsStr::from_bytes(b"caf\xe9.txt"));
+    fs::copy(FOX, &file)?;
+    let list = dir.path().join("files0");
+    fs::write(&list, [file.as_os_str().as_bytes(), b"\0"].concat())?;
+
+    Command::cargo_bin(PRG)?
+        .arg("--files0-from")
+        .arg(&list)
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("       1       9      48 "))
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_jobs() -> Result<()> {
+    run(
+        &["--jobs", "3", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_jobs_more_than_files() -> Result<()> {
+    run(
+        &["-j", "16", "-lL", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.lL.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_jobs_available_parallelism() -> Result<()> {
+    run(
+        &["-j", "0", "-cw", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.wc.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_files0_from_bad_entries() -> Result<()> {
+    let expected = fs::read_to_string("tests/expected/files0_bad.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-j", "2", "--files0-from", FILES0_BAD])
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predicate::str::is_match(
+            "invalid zero-length file name\n\
+             tests/inputs/missing.txt: .* [(]os error 2[)]\n",
+        )?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_preserves_argument_order() -> Result<()> {
+    // 大きなファイルを先頭に置いても引数の順番どおりに出力される
+    let mut args = vec!["-j", "4"];
+    for _ in 0..20 {
+        args.extend([ATLAMAL, EMPTY, FOX, WIDE]);
+    }
+    let sequential = Command::cargo_bin(PRG)?
+        .args(&args[2..])
+        .output()
+        .expect("fail");
+    let parallel = Command::cargo_bin(PRG)?.args(&args).output().expect("fail");
+    assert!(sequential.status.success());
+    assert!(parallel.status.success());
+    assert_eq!(
+        String::from_utf8(parallel.stdout)?,
+        String::from_utf8(sequential.stdout)?
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_format() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--format", "xml", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "invalid value 'xml' for '--format <FORMAT>'",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_table() -> Result<()> {
+    run(
+        &["--format", "table", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_json() -> Result<()> {
+    run(
+        &["--format", "json", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_csv() -> Result<()> {
+    run(
+        &["--format", "csv", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_csv_graphemes() -> Result<()> {
+    run(
+        &["--format", "csv", "--graphemes", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.graphemes.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn total_is_not_a_file_name() -> Result<()> {
+    // 合計の行はfileがnull(csvでは空欄)で、"total"という名前のファイルと区別できる
+    let output = Command::cargo_bin(PRG)?
+        .args(["--format", "json", FOX, ATLAMAL])
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    let total: serde_json::Value = serde_json::from_str(stdout.lines().last().unwrap())?;
+    assert_eq!(total["file"], serde_json::Value::Null);
+    assert_eq!(total["lines"], 5);
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["--format", "csv", FOX, ATLAMAL])
+        .output()?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.lines().last().unwrap().starts_with(",5,"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_csv_ignores_selected_columns() -> Result<()> {
+    run(
+        &["--format", "csv", "-l", WIDE],
+        "tests/expected/wide.txt.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_stdin_json() -> Result<()> {
+    let input = fs::read_to_string(FOX)?;
+    let expected = fs::read_to_string("tests/expected/fox.txt.stdin.json.out")?;
+
+    let output = Command::cargo_bin(PRG)?
+        .args(["--format", "json"])
+        .write_stdin(input)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn table_widens_columns() -> Result<()> {
+    // 列の幅は数える前にファイルの大きさの合計から決める
+    let mut file = tempfile::NamedTempFile::new()?;
+    file.write_all("\n".repeat(12_500_000).as_bytes())?;
+    let path = file.path().to_str().unwrap();
+    let output = Command::cargo_bin(PRG)?
+        .args(["-lc", path, FOX])
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    let expected = [
+        format!(" 12500000 12500000 {path}"),
+        "        1       48 tests/inputs/fox.txt".to_string(),
+        " 12500001 12500048 total".to_string(),
+    ];
+    assert_eq!(stdout, expected.join("\n") + "\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn table_prints_rows_as_counted() -> Result<()> {
+    // 標準入力を閉じる前に、先に数え終えたファイルの行が出力される
+    let mut child = process::Command::new(cargo_bin(PRG))
+        .args([FOX, "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdin = child.stdin.take().unwrap();
+    let closed = Arc::new(AtomicBool::new(false));
+    let closer = thread::spawn({
+        let closed = Arc::clone(&closed);
+        move || {
+            thread::sleep(Duration::from_secs(2));
+            closed.store(true, Ordering::SeqCst);
+            drop(stdin);
+        }
+    });
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line)?;
+    assert!(!closed.load(Ordering::SeqCst));
+    assert_eq!(line, "       1       9      48 tests/inputs/fox.txt\n");
+
+    closer.join().unwrap();
+    assert!(child.wait()?.success());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn japanese() -> Result<()> {
+    run(&[JAPANESE], "tests/expected/japanese.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn japanese_words_mode_whitespace() -> Result<()> {
+    run(
+        &["--words-mode", "whitespace", JAPANESE],
+        "tests/expected/japanese.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn japanese_words_mode_unicode() -> Result<()> {
+    run(
+        &["--words-mode", "unicode", JAPANESE],
+        "tests/expected/japanese.txt.unicode.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn japanese_words_mode_unicode_words() -> Result<()> {
+    run(
+        &["-w", "--words-mode", "unicode", JAPANESE],
+        "tests/expected/japanese.txt.unicode.w.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn japanese_chars_graphemes() -> Result<()> {
+    run(
+        &["-m", "--graphemes", JAPANESE],
+        "tests/expected/japanese.txt.m.graphemes.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn graphemes_chars_graphemes() -> Result<()> {
+    run(
+        &["-m", "--graphemes", GRAPHEMES],
+        "tests/expected/graphemes.txt.m.graphemes.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn japanese_graphemes_unicode_json() -> Result<()> {
+    run(
+        &[
+            "--format",
+            "json",
+            "--words-mode",
+            "unicode",
+            "--graphemes",
+            JAPANESE,
+            GRAPHEMES,
+        ],
+        "tests/expected/japanese_graphemes.unicode.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_stats_csv() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args(["--stats", "--format", "csv", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--stats cannot be used with --format csv",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn rows_stats() -> Result<()> {
+    run(&["--stats", ROWS], "tests/expected/rows.csv.stats.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn rows_stats_json() -> Result<()> {
+    run(
+        &["--stats", "--format", "json", ROWS],
+        "tests/expected/rows.csv.stats.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_stats() -> Result<()> {
+    run(
+        &["--stats", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.stats.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed() -> Result<()> {
+    run(&[MIXED], "tests/expected/mixed.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_count_unterminated() -> Result<()> {
+    run(
+        &["--count-unterminated", MIXED],
+        "tests/expected/mixed.txt.unterminated.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_atlamal_crlf() -> Result<()> {
+    run(
+        &["--crlf", MIXED, ATLAMAL],
+        "tests/expected/mixed_atlamal.crlf.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn compressed() -> Result<()> {
+    run(
+        &[FOX_GZ, FOX_ZST, FOX_XZ, FOX_GZIP, FOX_ATLAMAL_GZ],
+        "tests/expected/compressed.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn compressed_raw() -> Result<()> {
+    run(
+        &["--raw", FOX_GZ, FOX_ZST, FOX_XZ],
+        "tests/expected/compressed.raw.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn compressed_stdin() -> Result<()> {
+    let input = fs::read(FOX_ZST)?;
+    let expected = fs::read_to_string("tests/expected/fox.txt.zst.stdin.out")?;
+
+    let output = Command::cargo_bin(PRG)?
+        .write_stdin(input)
+        .output()
+        .expect("fail");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("invalid UTF-8");
+    assert_eq!(stdout, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn compressed_stdin_short_read() -> Result<()> {
+    let input = fs::read(FOX_GZ)?;
+    let expected = fs::read_to_string("tests/expected/fox.txt.zst.stdin.out")?;
+
+    let mut child = process::Command::new(cargo_bin(PRG))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    // 最初の読み込みがマジックバイトの途中で終わっても展開する
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(&input[..1])?;
+    stdin.flush()?;
+    thread::sleep(Duration::from_millis(200));
+    stdin.write_all(&input[1..])?;
+    drop(stdin);
+
+    let output = child.wait_with_output()?;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout)?, expected);
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn not_compressed_despite_extension() -> Result<()> {
+    // マジックバイトが一致しなければ拡張子が.gzでもそのまま数える
+    run(&[BAD_GZ, EMPTY_GZ], "tests/expected/not_compressed.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_bad_compressed_file() -> Result<()> {
+    Command::cargo_bin(PRG)?
+        .args([BAD_HEADER_GZ, FOX])
+        .assert()
+        .success()
+        .stderr(predicate::str::starts_with(format!("{BAD_HEADER_GZ}: ")))
+        .stdout(predicate::str::contains(FOX));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_truncated_compressed_file() -> Result<()> {
+    // 途中で壊れている圧縮ファイルも飛ばして残りを数え、最後に失敗として終了する
+    for format in ["table", "json"] {
+        let output = Command::cargo_bin(PRG)?
+            .args(["--format", format, FOX, TRUNCATED_GZ, ATLAMAL])
+            .output()?;
+        assert!(!output.status.success(), "{format}");
+        let stdout = String::from_utf8(output.stdout)?;
+        assert!(stdout.contains(FOX), "{format}: {stdout}");
+        assert!(stdout.contains(ATLAMAL), "{format}: {stdout}");
+        assert!(!stdout.contains(TRUNCATED_GZ), "{format}: {stdout}");
+        assert_eq!(stdout.lines().count(), 3, "{format}: {stdout}");
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(stderr.starts_with(&format!("{TRUNCATED_GZ}: ")), "{format}: {stderr}");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn jobs_stdin_more_than_once() -> Result<()> {
+    // 標準入力を複数回指定しても、全体を最初の"-"で数える
+    let input: String = (1..=200_000).map(|i| format!("{i}\n")).collect();
+    let args = ["-j", "2", "-", "-", FOX, "-"];
+    let sequential = Command::cargo_bin(PRG)?
+        .args(["-j", "1"])
+        .args(&args[2..])
+        .write_stdin(input.clone())
+        .output()
+        .expect("fail");
+    let parallel = Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(input)
+        .output()
+        .expect("fail");
+    assert!(sequential.status.success());
+    assert!(parallel.status.success());
+    assert_eq!(
+        String::from_utf8(parallel.stdout)?,
+        String::from_utf8(sequential.stdout)?
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn errors_in_argument_order() -> Result<()> {
+    // 標準出力と標準エラー出力をまとめて、エラーが引数の位置に出ることを確かめる
+    let bad = gen_bad_file();
+    let prg = assert_cmd::cargo::cargo_bin(PRG);
+    for format in ["table", "json", "csv"] {
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "'{}' --format {format} {FOX} {bad} {ATLAMAL} 2>&1",
+                prg.display()
+            ))
+            .output()?;
+        let stdout = String::from_utf8(output.stdout)?;
+        let lines: Vec<&str> = stdout.lines().collect();
+        let position = |needle: &str| {
+            lines.iter().position(|line| line.contains(needle)).unwrap()
+        };
+        assert!(position(FOX) < position(&bad), "{format}: {stdout}");
+        assert!(position(&bad) < position(ATLAMAL), "{format}: {stdout}");
+    }
+    Ok(())
+}